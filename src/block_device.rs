@@ -0,0 +1,77 @@
+use core::cell::RefCell;
+
+use embedded_hal::digital::v2::InputPin;
+use embedded_sdmmc::{Block, BlockCount, BlockIdx};
+
+use crate::bus::{Adtc, Bus, Read, Write};
+use crate::controller::Controller;
+use crate::error::WriteBlocksError;
+
+/// embedded-sdmmc block device backed by an initialized card
+pub struct BlockDevice<BUS, WP, DETECT> {
+    controller: RefCell<Controller<BUS, WP, DETECT>>,
+}
+
+impl<BUS, WP, DETECT> BlockDevice<BUS, WP, DETECT> {
+    pub fn new(controller: Controller<BUS, WP, DETECT>) -> Self {
+        Self { controller: RefCell::new(controller) }
+    }
+
+    /// Release the controller
+    pub fn free(self) -> Controller<BUS, WP, DETECT> {
+        self.controller.into_inner()
+    }
+}
+
+impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> embedded_sdmmc::BlockDevice
+    for BlockDevice<BUS, WP, DETECT>
+{
+    type Error = WriteBlocksError;
+
+    fn read(&self, blocks: &mut [Block], start_block_idx: BlockIdx) -> Result<(), Self::Error> {
+        let num_blocks = blocks.len();
+        let segments = blocks.iter_mut().map(|block| &mut block.contents[..]);
+        self.controller.borrow_mut().read_segments(start_block_idx.0, num_blocks, segments)?;
+        Ok(())
+    }
+
+    fn write(&self, blocks: &[Block], start_block_idx: BlockIdx) -> Result<(), Self::Error> {
+        let segments = blocks.iter().map(|block| &block.contents[..]);
+        self.controller.borrow_mut().write_segments(start_block_idx.0, blocks.len(), segments)
+    }
+
+    fn num_blocks(&self) -> Result<BlockCount, Self::Error> {
+        // Capacity in KBytes, 2TB cards have one more block than a block address can reach
+        let blocks = self.controller.borrow().card.capacity as u64 * 2;
+        Ok(BlockCount(blocks.min(u32::MAX as u64) as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_error::mci::MciError;
+    use embedded_sdmmc::BlockDevice as _;
+
+    use super::*;
+    use crate::controller::mock::{self, MockBus};
+
+    #[test]
+    fn read_multiple_blocks() {
+        let device = BlockDevice::new(mock::controller(MockBus::default(), false));
+        let mut blocks = [Block::new(), Block::new(), Block::new()];
+        assert!(device.read(&mut blocks, BlockIdx(4)).is_ok());
+        assert!(blocks.iter().all(|block| block.contents.iter().all(|&b| b == 0xA5)));
+        let controller = device.free();
+        assert_eq!(*controller.card.bus.commands.borrow(), [(13, 1 << 16), (18, 4), (12, 0)]);
+    }
+
+    #[test]
+    fn write_error_reports_written_blocks() {
+        let bus = MockBus { fail_transfer: Some(2), written_blocks: 2, ..MockBus::default() };
+        let device = BlockDevice::new(mock::controller(bus, false));
+        let blocks = [Block::new(), Block::new(), Block::new()];
+        let error = device.write(&blocks, BlockIdx(4)).unwrap_err();
+        assert!(matches!(error.error, MciError::WriteError));
+        assert_eq!(error.written, 2);
+    }
+}
@@ -1,11 +1,41 @@
 pub mod spi;
 
 use embedded_error::mci::MciError;
+use embedded_error::ImplError;
 
-use crate::command_arguments::mmc::BusWidth;
+use crate::command_arguments::mmc::{BootMode, BusWidth};
+use crate::command_arguments::sd::cmd6::DriverStrength;
 
 pub const SD_MMC_BLOCK_SIZE: usize = 512;
 
+/// Bus timing modes, DDR modes sample data on both clock edges
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Timing {
+    /// Default speed
+    Legacy,
+    HighSpeed,
+    /// eMMC HS200, SDR up to 200MHz
+    Hs200,
+    /// eMMC HS400, DDR up to 200MHz on 8 bits bus
+    Hs400,
+    /// eMMC HS400 with data strobe for command responses, no tuning needed
+    Hs400EnhancedStrobe,
+    /// eMMC high speed DDR up to 52MHz
+    Ddr52,
+    /// SD UHS-I modes at 1.8V signaling
+    UhsSdr12,
+    UhsSdr25,
+    UhsSdr50,
+    UhsSdr104,
+    UhsDdr50,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SignalVoltage {
+    _3V3,
+    _1V8,
+}
+
 pub trait Bus {
     /// Initialize MCI low level driver.
     fn init(&mut self) -> Result<(), MciError>;
@@ -89,7 +119,10 @@ pub trait Write {
     fn wait_until_write_finished(&mut self) -> Result<(), MciError>;
 }
 
-pub trait SpiBus: Bus + Adtc + Read + Write {}
+pub trait SpiBus: Bus + Adtc + Read + Write {
+    /// Accept R1 responses with the idle bit set, the card is idle only during initialization
+    fn set_idle_accepted(&mut self, accepted: bool);
+}
 
 pub trait SdMmcBus: Bus + Adtc + Read + Write {
     // TODO keep + get current selected slot
@@ -101,4 +134,67 @@ pub trait SdMmcBus: Bus + Adtc + Read + Write {
 
     /// Get 128 bits response of last command
     fn get_response128(&mut self) -> Result<[u32; 4], MciError>;
+
+    /// Whether the host supports a bus timing mode
+    fn is_timing_supported(&mut self, _timing: Timing) -> Result<bool, MciError> {
+        Ok(false)
+    }
+
+    /// Switch the signal voltage of the lines
+    /// For the SD voltage switch sequence the clock is stopped during the switch and restarted
+    /// 5ms after
+    fn set_signal_voltage(&mut self, _voltage: SignalVoltage) -> Result<(), MciError> {
+        Err(MciError::Impl(ImplError::InvalidConfiguration))
+    }
+
+    /// Apply a bus timing mode, the clock is set by select_device
+    fn set_timing(&mut self, _timing: Timing) -> Result<(), MciError> {
+        Err(MciError::Impl(ImplError::InvalidConfiguration))
+    }
+
+    /// SD card driver strength required by the board for UHS-I bus speed modes
+    fn driver_strength(&mut self) -> DriverStrength {
+        DriverStrength::TypeB
+    }
+
+    /// Maximum current in mA the host can supply to the SD card
+    fn max_current(&mut self) -> u32 {
+        200
+    }
+
+    /// Number of sampling points of the tuning, 0 if the sampling point cannot be tuned
+    fn tuning_points(&mut self) -> u8 {
+        0
+    }
+
+    /// Select the sampling point of the received data
+    fn set_tuning_point(&mut self, _point: u8) -> Result<(), MciError> {
+        Err(MciError::Impl(ImplError::InvalidConfiguration))
+    }
+
+    /// Whether the card holds DAT0 low to signal busy
+    /// False if the busy of R1b responses is already waited by send_command
+    fn is_busy(&mut self) -> Result<bool, MciError> {
+        Ok(false)
+    }
+
+    /// Prepare a boot operation of `block_amount` blocks, the boot acknowledge is expected before
+    /// the data if `ack`. For BootMode::CmdLineLow the CMD line is held low and the transfer
+    /// started, the data is then received with read_blocks.
+    fn boot_start(
+        &mut self,
+        mode: BootMode,
+        ack: bool,
+        _block_amount: u16,
+    ) -> Result<(), MciError> {
+        match (mode, ack) {
+            (BootMode::Alternative, false) => Ok(()),
+            _ => Err(MciError::Impl(ImplError::InvalidConfiguration)),
+        }
+    }
+
+    /// End the boot operation, the CMD line is released for BootMode::CmdLineLow
+    fn boot_stop(&mut self, _mode: BootMode) -> Result<(), MciError> {
+        Ok(())
+    }
 }
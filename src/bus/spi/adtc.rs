@@ -6,7 +6,10 @@ use crate::bus::Adtc;
 use crate::command_arguments::mci_command::MciCommand;
 
 use super::bus::SpiBus;
-use super::response::{BitField, R1Response, R1ResponseField};
+use super::response::{card_status, BitField, R1Response, R1ResponseField};
+
+// CMD13 responds with R2 in SPI mode, for both MCI and SPI definition of the command
+const SEND_STATUS_INDEX: u8 = 13;
 
 pub fn crc7(data: &[u8]) -> u8 {
     let mut crc = 0u8;
@@ -62,11 +65,17 @@ where
             .ok_or(MciError::CommandError(CommandOrDataError::Crc))?;
         r1.no(R1ResponseField::IllegalCommand)
             .ok_or(MciError::CommandError(CommandOrDataError::Index))?;
-        r1.no(R1ResponseField::Idle).ok_or(MciError::WriteError)?;
+        if !self.idle_accepted {
+            // A card back in idle state after initialization has been reset
+            r1.no(R1ResponseField::Idle).ok_or(MciError::WriteError)?;
+        }
         if mci_command.card_may_send_busy() {
             self.wait_busy()?;
         }
-        if mci_command.have_8bit_response() {
+        if mci_command.get_index() == SEND_STATUS_INDEX {
+            // R2 response, translated to the MCI card status format
+            self.last_response = card_status(r1, self.read_byte()?);
+        } else if mci_command.have_8bit_response() {
             self.last_response = u32::from_le(self.read_byte()? as u32);
         }
         if mci_command.have_32bit_response() {
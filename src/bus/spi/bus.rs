@@ -12,6 +12,8 @@ pub struct SpiBus<SPI, CS> {
     pub(crate) block_size: usize,
     pub(crate) num_blocks: usize,
     pub(crate) position: usize,
+    /// R1 idle bit is not an error, set during initialization
+    pub(crate) idle_accepted: bool,
 }
 
 impl<SPI, CS, E, OE> SpiBus<SPI, CS>
@@ -20,7 +22,15 @@ where
     CS: OutputPin<Error = OE>,
 {
     pub fn new(spi: SPI, cs: CS) -> Self {
-        Self { spi, cs, last_response: 0, block_size: 0, num_blocks: 0, position: 0 }
+        Self {
+            spi,
+            cs,
+            last_response: 0,
+            block_size: 0,
+            num_blocks: 0,
+            position: 0,
+            idle_accepted: false,
+        }
     }
 
     pub(crate) fn write_byte(&mut self, value: u8) -> Result<(), MciError> {
@@ -113,3 +123,13 @@ where
         Ok(self.last_response)
     }
 }
+
+impl<SPI, CS, E, OE> crate::bus::SpiBus for SpiBus<SPI, CS>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    CS: OutputPin<Error = OE>,
+{
+    fn set_idle_accepted(&mut self, accepted: bool) {
+        self.idle_accepted = accepted;
+    }
+}
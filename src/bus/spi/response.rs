@@ -49,6 +49,27 @@ impl Into<u8> for R1Response {
 
 impl BitField<R1ResponseField> for R1Response {}
 
+/// Convert a SPI R2 response (R1 followed by a second status byte) to the MCI card status format
+pub(crate) fn card_status(r1: R1Response, status: u8) -> u32 {
+    // (R1 bit, card status bit)
+    const R1_BITS: [(u8, u8); 6] = [(1, 13), (2, 22), (3, 23), (4, 28), (5, 30), (6, 31)];
+    // (R2 second byte bit, card status bit)
+    const STATUS_BITS: [(u8, u8); 8] =
+        [(0, 25), (1, 24), (2, 19), (3, 20), (4, 21), (5, 26), (6, 27), (7, 31)];
+    let mut val = 0u32;
+    for &(from, to) in R1_BITS.iter() {
+        val |= ((r1.0 as u32 >> from) & 1) << to;
+    }
+    for &(from, to) in STATUS_BITS.iter() {
+        val |= ((status as u32 >> from) & 1) << to;
+    }
+    if !r1.has(R1ResponseField::Idle) {
+        // Not busy once responded, then in transfer state and ready for data
+        val |= (4 << 9) | (1 << 8);
+    }
+    val
+}
+
 pub const BLOCK_READ_DATA_TOKEN: u8 = 0xFE;
 
 #[derive(Copy, Clone, Debug)]
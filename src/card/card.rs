@@ -1,8 +1,11 @@
 use bit_field::BitField;
 use embedded_hal::blocking::spi;
 
+use crate::bus::{SignalVoltage, Timing};
 use crate::command_arguments::mmc::BusWidth;
+use crate::registers::cid::CidRegister;
 use crate::registers::csd::CsdRegister;
+use crate::registers::ext_csd::ExtCsdRegister;
 
 use super::version::CardVersion;
 
@@ -68,6 +71,10 @@ pub enum State {
     Init,
     Unusable,
     NoCard,
+    /// Card is password locked, only lock card commands are accepted
+    Locked,
+    /// eMMC is in sleep state, only CMD0 and CMD5 are accepted
+    Sleep,
 }
 
 pub struct Card<BUS> {
@@ -87,10 +94,20 @@ pub struct Card<BUS> {
     pub version: CardVersion,
     /// Number of DATA lines on bus (MCI only)
     pub bus_width: BusWidth,
+    /// CID register
+    pub cid: CidRegister,
     /// CSD register
     pub csd: CsdRegister,
+    /// EXT_CSD register (MMC 4.0 and higher only)
+    pub ext_csd: ExtCsdRegister,
     /// High speed card
     pub high_speed: bool,
+    /// Bus timing mode
+    pub timing: Timing,
+    /// Signal voltage of the lines
+    pub signal_voltage: SignalVoltage,
+    /// Multiple block transfers are predefined with CMD23 instead of ended by CMD12
+    pub set_block_count: bool,
 }
 
 impl<WE, TE, SPI: spi::Write<u8, Error = WE> + spi::Transfer<u8, Error = TE>> Card<SPI> {
@@ -104,8 +121,13 @@ impl<WE, TE, SPI: spi::Write<u8, Error = WE> + spi::Transfer<u8, Error = TE>> Ca
             card_type: Type::default(),
             version: CardVersion::Unknown,
             bus_width: BusWidth::_1BIT,
+            cid: Default::default(),
             csd: Default::default(),
+            ext_csd: Default::default(),
             high_speed: false,
+            timing: Timing::Legacy,
+            signal_voltage: SignalVoltage::_3V3,
+            set_block_count: false,
         }
     }
 }
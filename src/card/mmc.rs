@@ -1,23 +1,20 @@
-use bit_field::BitField;
 use embedded_error::mci::MciError;
 
-use crate::bus::SdMmcBus;
+use crate::bus::{SdMmcBus, Timing};
 use crate::command_arguments::mmc::{Access, BusWidth, Cmd6};
 use crate::commands::{
-    MMC_CMD6_SWITCH, MMC_CMD8_SEND_EXT_CSD, SDMMC_CMD55_APP_CMD, SDMMC_MCI_CMD9_SEND_CSD,
-    SD_ACMD6_SET_BUS_WIDTH,
+    MMC_CMD6_SWITCH, MMC_CMD8_SEND_EXT_CSD, SDMMC_CMD10_SEND_CID, SDMMC_CMD2_ALL_SEND_CID,
+    SDMMC_CMD55_APP_CMD, SDMMC_MCI_CMD9_SEND_CSD, SD_ACMD6_SET_BUS_WIDTH,
 };
 use crate::mode_index::ModeIndex;
+use crate::registers::cid::CidRegister;
 use crate::registers::csd::CsdRegister;
+use crate::registers::ext_csd::EXT_CSD_SIZE;
 use crate::registers::sd::card_status::CardStatusRegister;
 
 use super::card::{Card, MMC_TRANS_MULTIPLIERS, SD_MMC_TRANS_UNITS};
 use super::version::{CardVersion, MmcVersion};
 
-pub const EXT_CSD_CARD_TYPE_INDEX: u32 = 196;
-pub const EXT_CSD_SEC_COUNT_INDEX: u32 = 212;
-pub const EXT_CSD_BSIZE: u32 = 512;
-
 impl<BUS: SdMmcBus> Card<BUS> {
     /// ACMD6 = Define the data bus width to be 4 bits
     pub fn set_data_bus_width_to_4_bits(&mut self) -> Result<(), MciError> {
@@ -36,10 +33,29 @@ impl<BUS: SdMmcBus> Card<BUS> {
         Ok(())
     }
 
+    /// CMD2: All cards send their card identification (CID), puts the card in identify mode
+    /// self.cid is updated
+    pub fn mci_all_send_cid(&mut self) -> Result<(), MciError> {
+        self.bus.send_command(SDMMC_CMD2_ALL_SEND_CID.into(), 0)?;
+        self.cid = CidRegister(self.bus.get_response128()?);
+        Ok(())
+    }
+
+    /// CMD10: Card sends its card identification (CID)
+    /// The card must be in stand-by state
+    /// self.cid is updated
+    pub fn mci_load_cid(&mut self) -> Result<(), MciError> {
+        let arg = (self.rca as u32) << 16;
+        self.bus.send_command(SDMMC_CMD10_SEND_CID.into(), arg)?;
+        self.cid = CidRegister(self.bus.get_response128()?);
+        Ok(())
+    }
+
     /// CMD6 for MMC - Switches the bus width mode
+    /// self.bus_width and self.ext_csd are updated
     pub fn set_bus_width(&mut self, bus_width: &BusWidth) -> Result<bool, MciError> {
         let mut arg = Cmd6::default();
-        arg.set_access(Access::SetBits)
+        arg.set_access(Access::WriteByte)
             .set_bus_width(&bus_width)
             .set_mode_index(ModeIndex::BusWidth);
         self.bus.send_command(MMC_CMD6_SWITCH.into(), arg.val)?;
@@ -48,13 +64,15 @@ impl<BUS: SdMmcBus> Card<BUS> {
             // Not supported, not a protocol error
             return Ok(false);
         }
-        self.bus_width = bus_width.clone();
+        self.ext_csd.set_byte(ModeIndex::BusWidth, u32::from(bus_width) as u8);
+        self.bus_width = bus_width.sdr();
         Ok(true)
     }
 
     /// CMD6 for MMC - Switches in high speed mode
     /// self.high_speed is updated
     /// self.clock is updated
+    /// self.ext_csd is updated
     pub fn set_high_speed(&mut self) -> Result<bool, MciError> {
         let mut arg = Cmd6::default();
         arg.set_access(Access::WriteByte)
@@ -66,41 +84,27 @@ impl<BUS: SdMmcBus> Card<BUS> {
             // Not supported, not a protocol error
             return Ok(false);
         }
+        self.ext_csd.set_byte(ModeIndex::HsTimingIndex, 1);
         self.high_speed = true;
+        self.timing = Timing::HighSpeed;
         self.clock = 52_000_000u32;
         Ok(true)
     }
 
     /// CMD8 - The card sends its EXT_CSD as a block of data
     /// Returns whether high speed can be handled by this
-    /// self.capacity is updated
+    /// self.ext_csd and self.capacity are updated
     pub fn load_extcsd(&mut self) -> Result<bool, MciError> {
-        self.bus.adtc_start(MMC_CMD8_SEND_EXT_CSD.into(), 0, 512, 1, false)?;
-
-        let mut index = 0u32;
-        let mut word = 0u32;
-        // Read in bytes (4 at a time) and not to a buffer to "fast forward" to the card type
-        while index < ((EXT_CSD_CARD_TYPE_INDEX + 4) / 4) {
-            word = self.bus.read_word()?;
-            index += 1;
+        self.bus.adtc_start(MMC_CMD8_SEND_EXT_CSD.into(), 0, EXT_CSD_SIZE as u16, 1, false)?;
+        for bytes in self.ext_csd.0.chunks_mut(4) {
+            bytes.copy_from_slice(&self.bus.read_word()?.to_le_bytes());
         }
-        let high_speed_capable =
-            (word >> ((EXT_CSD_CARD_TYPE_INDEX % 4) * 8)).get_bits(0..2) == 0x2; // 52MHz = 0x2, 26MHz = 0x1
 
         if self.csd.card_size() == 0xFFF {
             // For high capacity SD/MMC card, memory capacity = sec_count * 512 bytes
-            while index < (EXT_CSD_SEC_COUNT_INDEX + 4) / 4 {
-                word = self.bus.read_word()?;
-                index += 1;
-            }
-            self.capacity = word
-        }
-        // Forward to the end
-        while index < EXT_CSD_BSIZE / 4 {
-            self.bus.read_word()?;
-            index += 1;
+            self.capacity = self.ext_csd.sector_count() / 2; // In KBytes
         }
-        Ok(high_speed_capable)
+        Ok(self.ext_csd.hs52_supported())
     }
 
     /// Decode CSD for MMC
@@ -1,15 +1,35 @@
 use embedded_error::mci::MciError;
 
 use crate::bus::SpiBus;
-use crate::commands::SDMMC_SPI_CMD9_SEND_CSD;
+use crate::commands::{SDMMC_SPI_CMD10_SEND_CID, SDMMC_SPI_CMD9_SEND_CSD};
+use crate::registers::cid::CidRegister;
+use crate::registers::csd::CsdRegister;
 
 use super::card::Card;
 
 impl<BUS: SpiBus> Card<BUS> {
+    /// Read a 128 bits register sent as a data block, MSB first
+    fn spi_load_register(&mut self, command: u32) -> Result<[u32; 4], MciError> {
+        self.bus.adtc_start(command, (self.rca as u32) << 16, 16, 1, true)?;
+        let mut bytes = [0u8; 16];
+        self.bus.read_blocks(&mut bytes)?;
+        self.bus.wait_until_read_finished()?;
+        let mut register = [0u32; 4];
+        for (word, chunk) in register.iter_mut().rev().zip(bytes.chunks(4)) {
+            *word = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        Ok(register)
+    }
+
     pub fn spi_load_csd(&mut self) -> Result<(), MciError> {
-        self.bus.adtc_start(SDMMC_SPI_CMD9_SEND_CSD.into(), (self.rca as u32) << 16, 4, 1, true)?;
-        let bytes: &mut [u8; 16] = unsafe { core::mem::transmute(&mut self.csd.0) };
-        self.bus.read_blocks(bytes)?;
-        self.bus.wait_until_read_finished()
+        self.csd = CsdRegister(self.spi_load_register(SDMMC_SPI_CMD9_SEND_CSD.into())?);
+        Ok(())
+    }
+
+    /// CMD10: Card sends its card identification (CID)
+    /// self.cid is updated
+    pub fn spi_load_cid(&mut self) -> Result<(), MciError> {
+        self.cid = CidRegister(self.spi_load_register(SDMMC_SPI_CMD10_SEND_CID.into())?);
+        Ok(())
     }
 }
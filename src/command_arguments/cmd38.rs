@@ -0,0 +1,132 @@
+use bit_field::BitField;
+
+/// Kind of erase of CMD38
+#[derive(Copy, Clone, PartialEq)]
+pub enum EraseMode {
+    /// Erase of the whole erase groups
+    Erase,
+    /// Discard, the content of the blocks becomes undefined
+    Discard,
+    /// Trim of the write blocks - MMC card
+    Trim,
+    /// Full User area Logical Erase, the address range is ignored - SD card
+    Fule,
+    /// Erase of the whole erase groups including the unmapped copies - MMC card
+    SecureErase,
+    /// Secure trim step 1, marks the write blocks for secure trim - MMC card
+    SecureTrim1,
+    /// Secure trim step 2, purges all the marked blocks - MMC card
+    /// The range of step 1 shall be passed again, the purge timeout is proportional to its size
+    SecureTrim2,
+}
+
+impl EraseMode {
+    /// Whether the mode requires the secure purge feature - MMC card
+    pub fn secure(&self) -> bool {
+        matches!(self, EraseMode::SecureErase | EraseMode::SecureTrim1 | EraseMode::SecureTrim2)
+    }
+}
+
+/// CMD38 erase argument
+#[derive(Default)]
+pub struct Cmd38 {
+    pub val: u32,
+}
+
+impl Cmd38 {
+    /// Argument for a SD card, None if the mode is not supported by SD
+    pub fn sd(mode: EraseMode) -> Option<Self> {
+        let function = match mode {
+            EraseMode::Erase => 0,
+            EraseMode::Discard => 1,
+            EraseMode::Fule => 2,
+            EraseMode::Trim
+            | EraseMode::SecureErase
+            | EraseMode::SecureTrim1
+            | EraseMode::SecureTrim2 => return None,
+        };
+        let mut arg = Self::default();
+        arg.val.set_bits(0..2, function);
+        Some(arg)
+    }
+
+    /// Argument for a MMC card, None if the mode is not supported by MMC
+    pub fn mmc(mode: EraseMode) -> Option<Self> {
+        let mut arg = Self::default();
+        match mode {
+            EraseMode::Erase => arg.set_identify_write_blocks(false),
+            EraseMode::Trim => arg.set_identify_write_blocks(true),
+            EraseMode::Discard => arg.set_identify_write_blocks(true).set_discard(true),
+            EraseMode::SecureErase => arg.set_secure(true),
+            EraseMode::SecureTrim1 => arg.set_secure(true).set_identify_write_blocks(true),
+            EraseMode::SecureTrim2 => arg.set_secure(true).set_force_garbage_collect(true),
+            EraseMode::Fule => return None,
+        };
+        Some(arg)
+    }
+
+    /// Erase write blocks rather than erase groups - MMC card
+    pub fn set_identify_write_blocks(&mut self, enabled: bool) -> &mut Self {
+        self.val.set_bit(0, enabled);
+        self
+    }
+
+    pub fn identify_write_blocks(&self) -> bool {
+        self.val.get_bit(0)
+    }
+
+    /// Discard rather than trim the write blocks - MMC card
+    pub fn set_discard(&mut self, enabled: bool) -> &mut Self {
+        self.val.set_bit(1, enabled);
+        self
+    }
+
+    pub fn discard(&self) -> bool {
+        self.val.get_bit(1)
+    }
+
+    /// Purge the erased blocks from the unmapped memory too - MMC card
+    pub fn set_secure(&mut self, enabled: bool) -> &mut Self {
+        self.val.set_bit(31, enabled);
+        self
+    }
+
+    pub fn secure(&self) -> bool {
+        self.val.get_bit(31)
+    }
+
+    /// Purge the blocks marked by secure trim step 1 - MMC card
+    pub fn set_force_garbage_collect(&mut self, enabled: bool) -> &mut Self {
+        self.val.set_bit(15, enabled);
+        self
+    }
+
+    pub fn force_garbage_collect(&self) -> bool {
+        self.val.get_bit(15)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sd_arguments() {
+        assert_eq!(Cmd38::sd(EraseMode::Erase).map(|arg| arg.val), Some(0));
+        assert_eq!(Cmd38::sd(EraseMode::Discard).map(|arg| arg.val), Some(1));
+        assert_eq!(Cmd38::sd(EraseMode::Fule).map(|arg| arg.val), Some(2));
+        assert!(Cmd38::sd(EraseMode::Trim).is_none());
+        assert!(Cmd38::sd(EraseMode::SecureErase).is_none());
+    }
+
+    #[test]
+    fn mmc_arguments() {
+        assert_eq!(Cmd38::mmc(EraseMode::Erase).map(|arg| arg.val), Some(0x0000_0000));
+        assert_eq!(Cmd38::mmc(EraseMode::Trim).map(|arg| arg.val), Some(0x0000_0001));
+        assert_eq!(Cmd38::mmc(EraseMode::Discard).map(|arg| arg.val), Some(0x0000_0003));
+        assert_eq!(Cmd38::mmc(EraseMode::SecureErase).map(|arg| arg.val), Some(0x8000_0000));
+        assert_eq!(Cmd38::mmc(EraseMode::SecureTrim1).map(|arg| arg.val), Some(0x8000_0001));
+        assert_eq!(Cmd38::mmc(EraseMode::SecureTrim2).map(|arg| arg.val), Some(0x8000_8000));
+        assert!(Cmd38::mmc(EraseMode::Fule).is_none());
+    }
+}
@@ -0,0 +1,114 @@
+use bit_field::BitField;
+
+pub const MAX_PASSWORD_LENGTH: usize = 16;
+
+const SET_PWD: usize = 0;
+const CLR_PWD: usize = 1;
+const LOCK_UNLOCK: usize = 2;
+const ERASE: usize = 3;
+
+/// CMD42 lock card data structure
+pub struct Cmd42 {
+    data: [u8; 2 + 2 * MAX_PASSWORD_LENGTH],
+    length: usize,
+}
+
+impl Cmd42 {
+    fn new(mode: Option<usize>, passwords: &[&[u8]]) -> Option<Self> {
+        let mut cmd = Cmd42 { data: [0u8; 2 + 2 * MAX_PASSWORD_LENGTH], length: 2 };
+        if let Some(bit) = mode {
+            cmd.data[0].set_bit(bit, true);
+        }
+        for password in passwords.iter() {
+            if password.len() > MAX_PASSWORD_LENGTH {
+                return None;
+            }
+            cmd.data[cmd.length..cmd.length + password.len()].copy_from_slice(password);
+            cmd.length += password.len();
+        }
+        if cmd.length == 2 {
+            // A password is mandatory except for forced erase
+            return None;
+        }
+        cmd.data[1] = (cmd.length - 2) as u8;
+        Some(cmd)
+    }
+
+    /// Replace the `old` password by `new`, `old` is empty if no password is set
+    pub fn set_password(old: &[u8], new: &[u8]) -> Option<Self> {
+        Self::new(Some(SET_PWD), &[old, new])
+    }
+
+    pub fn clear_password(password: &[u8]) -> Option<Self> {
+        Self::new(Some(CLR_PWD), &[password])
+    }
+
+    pub fn lock(password: &[u8]) -> Option<Self> {
+        Self::new(Some(LOCK_UNLOCK), &[password])
+    }
+
+    pub fn unlock(password: &[u8]) -> Option<Self> {
+        Self::new(None, &[password])
+    }
+
+    /// Erase all the card content along with the password
+    pub fn forced_erase() -> Self {
+        let mut cmd = Cmd42 { data: [0u8; 2 + 2 * MAX_PASSWORD_LENGTH], length: 1 };
+        cmd.data[0].set_bit(ERASE, true);
+        cmd
+    }
+
+    pub fn set_password_mode(&self) -> bool {
+        self.data[0].get_bit(SET_PWD)
+    }
+
+    pub fn clear_password_mode(&self) -> bool {
+        self.data[0].get_bit(CLR_PWD)
+    }
+
+    pub fn lock_mode(&self) -> bool {
+        self.data[0].get_bit(LOCK_UNLOCK)
+    }
+
+    pub fn erase_mode(&self) -> bool {
+        self.data[0].get_bit(ERASE)
+    }
+
+    /// Data block to send
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.length]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_block() {
+        let cmd = Cmd42::set_password(b"ab", b"cde").unwrap();
+        assert!(cmd.set_password_mode());
+        assert_eq!(cmd.as_bytes(), &[0x01, 5, b'a', b'b', b'c', b'd', b'e']);
+        let cmd = Cmd42::clear_password(b"pw").unwrap();
+        assert!(cmd.clear_password_mode());
+        assert_eq!(cmd.as_bytes(), &[0x02, 2, b'p', b'w']);
+        let cmd = Cmd42::lock(b"pw").unwrap();
+        assert!(cmd.lock_mode());
+        assert_eq!(cmd.as_bytes(), &[0x04, 2, b'p', b'w']);
+        let cmd = Cmd42::unlock(b"pw").unwrap();
+        assert!(!cmd.lock_mode());
+        assert_eq!(cmd.as_bytes(), &[0x00, 2, b'p', b'w']);
+        let cmd = Cmd42::forced_erase();
+        assert!(cmd.erase_mode());
+        assert_eq!(cmd.as_bytes(), &[0x08]);
+    }
+
+    #[test]
+    fn invalid_passwords() {
+        assert!(Cmd42::unlock(b"").is_none());
+        assert!(Cmd42::set_password(b"", b"").is_none());
+        assert!(Cmd42::lock(&[0u8; MAX_PASSWORD_LENGTH + 1]).is_none());
+        let cmd = Cmd42::set_password(&[1u8; MAX_PASSWORD_LENGTH], &[2u8; MAX_PASSWORD_LENGTH]);
+        assert_eq!(cmd.map(|cmd| cmd.as_bytes().len()), Some(2 + 2 * MAX_PASSWORD_LENGTH));
+    }
+}
@@ -26,11 +26,36 @@ impl From<u32> for Access {
     }
 }
 
+/// DDR bus widths are only written to EXT_CSD BUS_WIDTH, the card and the host keep the
+/// number of lines with a DDR timing
+#[allow(non_camel_case_types)]
 #[derive(Copy, Clone, PartialOrd, PartialEq)]
 pub enum BusWidth {
     _1BIT = 0,
     _4BIT = 1,
     _8BIT = 2,
+    _4BIT_DDR = 5,
+    _8BIT_DDR = 6,
+}
+
+impl BusWidth {
+    /// DDR bus width with the same number of lines, 1 bit bus width has no DDR mode
+    pub fn ddr(&self) -> Self {
+        match self {
+            BusWidth::_4BIT => BusWidth::_4BIT_DDR,
+            BusWidth::_8BIT => BusWidth::_8BIT_DDR,
+            width => *width,
+        }
+    }
+
+    /// SDR bus width with the same number of lines
+    pub fn sdr(&self) -> Self {
+        match self {
+            BusWidth::_4BIT_DDR => BusWidth::_4BIT,
+            BusWidth::_8BIT_DDR => BusWidth::_8BIT,
+            width => *width,
+        }
+    }
 }
 
 impl From<u32> for BusWidth {
@@ -39,6 +64,8 @@ impl From<u32> for BusWidth {
             0 => BusWidth::_1BIT,
             1 => BusWidth::_4BIT,
             2 => BusWidth::_8BIT,
+            5 => BusWidth::_4BIT_DDR,
+            6 => BusWidth::_8BIT_DDR,
             _ => unsafe { unreachable_unchecked() },
         }
     }
@@ -50,6 +77,70 @@ impl From<&BusWidth> for u32 {
             BusWidth::_1BIT => 0,
             BusWidth::_4BIT => 1,
             BusWidth::_8BIT => 2,
+            BusWidth::_4BIT_DDR => 5,
+            BusWidth::_8BIT_DDR => 6,
+        }
+    }
+}
+
+/// EXT_CSD POWER_OFF_NOTIFICATION values
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PowerOffNotification {
+    NoPowerNotification = 0,
+    PoweredOn = 1,
+    PowerOffShort = 2,
+    PowerOffLong = 3,
+    SleepNotification = 4,
+}
+
+/// Boot operation started by the host
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BootMode {
+    /// CMD line held low for the whole boot operation
+    CmdLineLow,
+    /// CMD0 with the boot initiation argument
+    Alternative,
+}
+
+/// Bus timing of the boot operation, BOOT_MODE field of BOOT_BUS_CONDITIONS
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BootTiming {
+    Legacy = 0,
+    HighSpeed = 1,
+    Ddr = 2,
+}
+
+/// eMMC hardware partitions
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Partition {
+    User,
+    Boot1,
+    Boot2,
+    Rpmb,
+    /// General purpose partition 0 to 3
+    GeneralPurpose(u8),
+}
+
+impl From<u8> for Partition {
+    fn from(val: u8) -> Self {
+        match val {
+            0 => Partition::User,
+            1 => Partition::Boot1,
+            2 => Partition::Boot2,
+            3 => Partition::Rpmb,
+            n => Partition::GeneralPurpose(n - 4),
+        }
+    }
+}
+
+impl From<Partition> for u8 {
+    fn from(val: Partition) -> Self {
+        match val {
+            Partition::User => 0,
+            Partition::Boot1 => 1,
+            Partition::Boot2 => 2,
+            Partition::Rpmb => 3,
+            Partition::GeneralPurpose(n) => 4 + n,
         }
     }
 }
@@ -95,6 +186,15 @@ impl Cmd6 {
         self.val.get_bits(8..=15) > 0
     }
 
+    pub fn set_value(&mut self, value: u8) -> &mut Self {
+        self.val.set_bits(8..=15, value as u32);
+        self
+    }
+
+    pub fn value(&self) -> u8 {
+        self.val.get_bits(8..=15) as u8
+    }
+
     pub fn set_cmd(&mut self, cmd: u8) -> &mut Self {
         self.val.set_bits(0..=2, cmd as u32);
         self
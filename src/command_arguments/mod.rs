@@ -1,4 +1,7 @@
+pub mod cmd38;
+pub mod cmd42;
 pub mod mci_command;
 pub mod mmc;
+pub mod rpmb;
 pub mod sd;
 pub mod sdio;
@@ -0,0 +1,184 @@
+pub const RPMB_FRAME_SIZE: usize = 512;
+/// Data size of a frame, RPMB addresses are in units of half sectors
+pub const RPMB_DATA_SIZE: usize = 256;
+pub const RPMB_KEY_SIZE: usize = 32;
+pub const RPMB_NONCE_SIZE: usize = 16;
+
+const KEY_MAC: usize = 196;
+const DATA: usize = KEY_MAC + RPMB_KEY_SIZE;
+const NONCE: usize = DATA + RPMB_DATA_SIZE;
+const WRITE_COUNTER: usize = NONCE + RPMB_NONCE_SIZE;
+const ADDRESS: usize = WRITE_COUNTER + 4;
+const BLOCK_COUNT: usize = ADDRESS + 2;
+const RESULT: usize = BLOCK_COUNT + 2;
+const REQUEST_RESPONSE: usize = RESULT + 2;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum RpmbRequest {
+    ProgramKey = 1,
+    ReadWriteCounter = 2,
+    AuthenticatedWrite = 3,
+    AuthenticatedRead = 4,
+    ReadResult = 5,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum RpmbResult {
+    Ok = 0,
+    GeneralFailure = 1,
+    AuthenticationFailure = 2,
+    CounterFailure = 3,
+    AddressFailure = 4,
+    WriteFailure = 5,
+    ReadFailure = 6,
+    KeyNotProgrammed = 7,
+}
+
+impl From<u16> for RpmbResult {
+    fn from(val: u16) -> Self {
+        match val {
+            0 => RpmbResult::Ok,
+            2 => RpmbResult::AuthenticationFailure,
+            3 => RpmbResult::CounterFailure,
+            4 => RpmbResult::AddressFailure,
+            5 => RpmbResult::WriteFailure,
+            6 => RpmbResult::ReadFailure,
+            7 => RpmbResult::KeyNotProgrammed,
+            _ => RpmbResult::GeneralFailure,
+        }
+    }
+}
+
+/// RPMB data frame, all fields are big endian
+pub struct RpmbFrame(pub [u8; RPMB_FRAME_SIZE]);
+
+impl Default for RpmbFrame {
+    fn default() -> Self {
+        RpmbFrame([0u8; RPMB_FRAME_SIZE])
+    }
+}
+
+impl RpmbFrame {
+    pub fn new(request: RpmbRequest) -> Self {
+        let mut frame = Self::default();
+        frame.set_request(request);
+        frame
+    }
+
+    pub fn set_key_mac(&mut self, key_mac: &[u8; RPMB_KEY_SIZE]) -> &mut Self {
+        self.0[KEY_MAC..DATA].copy_from_slice(key_mac);
+        self
+    }
+
+    /// Authentication key of key programming requests, MAC of other frames
+    pub fn key_mac(&self) -> &[u8] {
+        &self.0[KEY_MAC..DATA]
+    }
+
+    pub fn set_data(&mut self, data: &[u8]) -> &mut Self {
+        self.0[DATA..DATA + data.len()].copy_from_slice(data);
+        self
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.0[DATA..NONCE]
+    }
+
+    pub fn set_nonce(&mut self, nonce: &[u8; RPMB_NONCE_SIZE]) -> &mut Self {
+        self.0[NONCE..WRITE_COUNTER].copy_from_slice(nonce);
+        self
+    }
+
+    pub fn nonce(&self) -> &[u8] {
+        &self.0[NONCE..WRITE_COUNTER]
+    }
+
+    pub fn set_write_counter(&mut self, counter: u32) -> &mut Self {
+        self.0[WRITE_COUNTER..ADDRESS].copy_from_slice(&counter.to_be_bytes());
+        self
+    }
+
+    pub fn write_counter(&self) -> u32 {
+        let bytes = &self.0[WRITE_COUNTER..ADDRESS];
+        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    pub fn set_address(&mut self, address: u16) -> &mut Self {
+        self.0[ADDRESS..BLOCK_COUNT].copy_from_slice(&address.to_be_bytes());
+        self
+    }
+
+    /// Address in half sectors
+    pub fn address(&self) -> u16 {
+        u16::from_be_bytes([self.0[ADDRESS], self.0[ADDRESS + 1]])
+    }
+
+    pub fn set_block_count(&mut self, count: u16) -> &mut Self {
+        self.0[BLOCK_COUNT..RESULT].copy_from_slice(&count.to_be_bytes());
+        self
+    }
+
+    pub fn block_count(&self) -> u16 {
+        u16::from_be_bytes([self.0[BLOCK_COUNT], self.0[BLOCK_COUNT + 1]])
+    }
+
+    pub fn result(&self) -> RpmbResult {
+        (u16::from_be_bytes([self.0[RESULT], self.0[RESULT + 1]]) & 0x7F).into()
+    }
+
+    pub fn write_counter_expired(&self) -> bool {
+        self.0[RESULT + 1] & 0x80 > 0
+    }
+
+    pub fn set_request(&mut self, request: RpmbRequest) -> &mut Self {
+        self.0[REQUEST_RESPONSE..].copy_from_slice(&(request as u16).to_be_bytes());
+        self
+    }
+
+    /// Whether the frame is the response to `request`
+    pub fn is_response(&self, request: RpmbRequest) -> bool {
+        self.0[REQUEST_RESPONSE..] == ((request as u16) << 8).to_be_bytes()
+    }
+
+    /// Part of the frame covered by the MAC, from data to request/response type
+    pub fn authenticated(&self) -> &[u8] {
+        &self.0[DATA..]
+    }
+
+    /// Data block to send
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_frame() {
+        let mut frame = RpmbFrame::new(RpmbRequest::AuthenticatedRead);
+        frame.set_write_counter(0x0102_0304).set_address(0x1234).set_block_count(2);
+        frame.set_nonce(&[0xAA; RPMB_NONCE_SIZE]).set_key_mac(&[0x55; RPMB_KEY_SIZE]);
+        assert_eq!(&frame.0[196..228], &[0x55; RPMB_KEY_SIZE]);
+        assert_eq!(&frame.0[484..500], &[0xAA; RPMB_NONCE_SIZE]);
+        assert_eq!(&frame.0[500..], &[1, 2, 3, 4, 0x12, 0x34, 0x00, 0x02, 0, 0, 0x00, 0x04]);
+        assert_eq!(frame.write_counter(), 0x0102_0304);
+        assert_eq!(frame.address(), 0x1234);
+        assert_eq!(frame.block_count(), 2);
+        assert_eq!(frame.authenticated().len(), 284);
+    }
+
+    #[test]
+    fn response_frame() {
+        let mut frame = RpmbFrame::default();
+        frame.0[508..].copy_from_slice(&[0x00, 0x82, 0x03, 0x00]);
+        assert!(frame.is_response(RpmbRequest::AuthenticatedWrite));
+        assert!(!frame.is_response(RpmbRequest::AuthenticatedRead));
+        assert_eq!(frame.result(), RpmbResult::AuthenticationFailure);
+        assert!(frame.write_counter_expired());
+        frame.0[509] = 0x07;
+        assert_eq!(frame.result(), RpmbResult::KeyNotProgrammed);
+        assert!(!frame.write_counter_expired());
+    }
+}
@@ -26,7 +26,55 @@ impl From<bool> for Cmd6Mode {
     }
 }
 
+/// Function group 1 - bus speed modes
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BusSpeedMode {
+    /// Default speed, 25MHz
+    Sdr12 = 0,
+    /// High speed, 50MHz
+    Sdr25 = 1,
+    Sdr50 = 2,
+    Sdr104 = 3,
+    Ddr50 = 4,
+}
+
+/// Function group 3 - driver strength
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DriverStrength {
+    /// Default, 50 ohm
+    TypeB = 0,
+    /// 33 ohm
+    TypeA = 1,
+    /// 66 ohm
+    TypeC = 2,
+    /// 100 ohm
+    TypeD = 3,
+}
+
+/// Function group 4 - current limit
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CurrentLimit {
+    /// Default
+    _200mA = 0,
+    _400mA = 1,
+    _600mA = 2,
+    _800mA = 3,
+}
+
+impl CurrentLimit {
+    pub fn milliamps(&self) -> u32 {
+        200 * (*self as u32 + 1)
+    }
+}
+
 impl Cmd6 {
+    /// All function groups are left unchanged
+    pub fn no_influence(mode: Cmd6Mode) -> Self {
+        let mut cmd = Cmd6 { val: 0x00FF_FFFF };
+        cmd.set_mode(mode);
+        cmd
+    }
+
     pub fn set_function_group_1_access_mode(&mut self, high_speed: bool) -> &mut Self {
         self.val.set_bits(0..=3, high_speed as u32);
         self
@@ -36,6 +84,16 @@ impl Cmd6 {
         self.val.get_bits(0..=3) > 0
     }
 
+    /// Function of group 1, 0xF for no influence
+    pub fn set_function_group1(&mut self, function: u8) -> &mut Self {
+        self.val.set_bits(0..=3, function as u32);
+        self
+    }
+
+    pub fn function_group1(&self) -> u8 {
+        self.val.get_bits(0..=3) as u8
+    }
+
     pub fn set_function_group2_command_system(&mut self, no_influence: bool) -> &mut Self {
         self.val
             .set_bits(4..=7, if no_influence { 0xF } else { 0x0 });
@@ -56,6 +114,16 @@ impl Cmd6 {
         self.val.get_bits(8..=11) == 0xF
     }
 
+    pub fn set_driver_strength(&mut self, strength: DriverStrength) -> &mut Self {
+        self.val.set_bits(8..=11, strength as u32);
+        self
+    }
+
+    /// Function of group 3, 0xF for no influence
+    pub fn driver_strength(&self) -> u8 {
+        self.val.get_bits(8..=11) as u8
+    }
+
     pub fn set_function_group4(&mut self, no_influence: bool) -> &mut Self {
         self.val
             .set_bits(12..=15, if no_influence { 0xF } else { 0x0 });
@@ -66,6 +134,16 @@ impl Cmd6 {
         self.val.get_bits(12..=15) == 0xF
     }
 
+    pub fn set_current_limit(&mut self, limit: CurrentLimit) -> &mut Self {
+        self.val.set_bits(12..=15, limit as u32);
+        self
+    }
+
+    /// Function of group 4, 0xF for no influence
+    pub fn current_limit(&self) -> u8 {
+        self.val.get_bits(12..=15) as u8
+    }
+
     pub fn set_function_group5(&mut self, no_influence: bool) -> &mut Self {
         self.val
             .set_bits(16..=19, if no_influence { 0xF } else { 0x0 });
@@ -29,6 +29,12 @@ pub const SDMMC_MCI_CMD0_GO_IDLE_STATE: Command<NoResponse, OpenDrain> = Command
     response: NoResponse,
     flag: OpenDrain,
 };
+// MMC Cmd0(bc): Alternative boot operation, the boot partition is sent on the data lines
+pub const MMC_CMD0_BOOT_INITIATION: Command<NoResponse, MultiBlock> = Command {
+    number: 0,
+    response: NoResponse,
+    flag: MultiBlock,
+};
 
 // MMC Cmd1(bcr, R3): Ask the card to send its Operating Conditions
 pub const MMC_SPI_CMD1_SEND_OP_COND: Command<CmdR1R6, NoFlag> = Command {
@@ -95,7 +101,7 @@ pub const SDMMC_CMD7_DESELECT_CARD_CMD: Command<CmdR1R6, NoFlag> = Command {
 
 // MMC Cmd8(adtc, R1): Send EXT_CSD register as a block of data
 pub const MMC_CMD8_SEND_EXT_CSD: Command<CmdR1R6, SingleBlock> = Command {
-    number: 7,
+    number: 8,
     response: CmdR1R6,
     flag: SingleBlock,
 };
@@ -119,6 +125,13 @@ pub const SDMMC_MCI_CMD9_SEND_CSD: Command<CmdR2, NoFlag> = Command {
     flag: NoFlag,
 };
 
+// Cmd10 SPI (R1): Addressed card sends its card identification (CID)
+pub const SDMMC_SPI_CMD10_SEND_CID: Command<CmdR1R6, SingleBlock> = Command {
+    number: 10,
+    response: CmdR1R6,
+    flag: SingleBlock,
+};
+
 // Cmd10(ac, R2): Addressed card sends its card identification (CID)
 pub const SDMMC_CMD10_SEND_CID: Command<CmdR2, NoFlag> = Command {
     number: 10,
@@ -141,6 +154,11 @@ pub const SD_CMD11_READ_DAT_UNTIL_STOP: Command<CmdR1R6, NoFlag> = Command {
     response: CmdR1R6,
     flag: NoFlag,
 };
+pub const SD_CMD11_VOLTAGE_SWITCH: Command<CmdR1R6, NoFlag> = Command {
+    number: 11,
+    response: CmdR1R6,
+    flag: NoFlag,
+};
 
 // Cmd12(ac, R1b): Force the card to stop transmission
 pub const SDMMC_CMD12_STOP_TRANSMISSION: Command<CmdR1B, NoFlag> = Command {
@@ -163,10 +181,10 @@ pub const SDMMC_MCI_CMD13_SEND_STATUS: Command<CmdR1R6, NoFlag> = Command {
 };
 
 // MMC Cmd14(adtc, R1): Read the reversed bus testing data pattern from a card.
-pub const MMC_CMD14_BUSTEST_R: Command<CmdR1R6, NoFlag> = Command {
+pub const MMC_CMD14_BUSTEST_R: Command<CmdR1R6, SingleBlock> = Command {
     number: 14,
     response: CmdR1R6,
-    flag: NoFlag,
+    flag: SingleBlock,
 };
 
 // Cmd15(ac): Send an addressed card into the Inactive State.
@@ -177,16 +195,30 @@ pub const SDMMC_CMD15_GO_INACTIVE_STATE: Command<NoResponse, OpenDrain> = Comman
     flag: OpenDrain,
 };
 
+// MMC Cmd21(adtc, R1): Send the tuning block pattern for HS200
+pub const MMC_CMD21_SEND_TUNING_BLOCK: Command<CmdR1R6, SingleBlock> = Command {
+    number: 21,
+    response: CmdR1R6,
+    flag: SingleBlock,
+};
+
+// SD Cmd19(adtc, R1): Send the tuning block pattern for SDR50 and SDR104
+pub const SD_CMD19_SEND_TUNING_BLOCK: Command<CmdR1R6, SingleBlock> = Command {
+    number: 19,
+    response: CmdR1R6,
+    flag: SingleBlock,
+};
+
 // MMC Cmd19(adtc, R1): Send the bus test data pattern
-pub const MMC_CMD19_BUSTEST_W: Command<CmdR1R6, NoFlag> = Command {
+pub const MMC_CMD19_BUSTEST_W: Command<CmdR1R6, WriteSingleBlock> = Command {
     number: 19,
     response: CmdR1R6,
-    flag: NoFlag,
+    flag: WriteSingleBlock,
 };
 
 // Cmd58(R3): Reads the OCR register of a card
 pub const SDMMC_SPI_CMD58_READ_OCR: Command<CmdR3R4, NoFlag> = Command {
-    number: 19,
+    number: 58,
     response: CmdR3R4,
     flag: NoFlag,
 };
@@ -330,10 +362,10 @@ pub const SDMMC_CMD29_CLR_WRITE_PROT: Command<CmdR1B, NoFlag> = Command {
 };
 
 // Cmd30(adtc, R1b): Send write protection
-pub const SDMMC_CMD30_SEND_WRITE_PROT: Command<CmdR1R6, NoFlag> = Command {
+pub const SDMMC_CMD30_SEND_WRITE_PROT: Command<CmdR1R6, SingleBlock> = Command {
     number: 30,
     response: CmdR1R6,
-    flag: NoFlag,
+    flag: SingleBlock,
 };
 
 //
@@ -341,10 +373,10 @@ pub const SDMMC_CMD30_SEND_WRITE_PROT: Command<CmdR1R6, NoFlag> = Command {
 //
 
 // Cmd42(adtc, R1): Used to set/reset the password or lock/unlock the card.
-pub const SDMMC_CMD42_LOCK_UNLOCK: Command<CmdR1R6, NoFlag> = Command {
+pub const SDMMC_CMD42_LOCK_UNLOCK: Command<CmdR1R6, WriteSingleBlock> = Command {
     number: 42,
     response: CmdR1R6,
-    flag: NoFlag,
+    flag: WriteSingleBlock,
 };
 
 //
@@ -391,17 +423,17 @@ pub const SD_ACMD6_SET_BUS_WIDTH: Command<CmdR1R6, NoFlag> = Command {
 };
 
 // ACMD13(adtc, R1): Send the SD Status
-pub const SD_ACMD13_SD_STATUS: Command<CmdR1R6, NoFlag> = Command {
+pub const SD_ACMD13_SD_STATUS: Command<CmdR1R6, SingleBlock> = Command {
     number: 13,
     response: CmdR1R6,
-    flag: NoFlag,
+    flag: SingleBlock,
 };
 
 //  ACMD22(adtc, R1): Send the number of the written (with-out errors) write blocks.
-pub const SD_ACMD22_SEND_NUM_WR_BLOCKS: Command<CmdR1R6, NoFlag> = Command {
+pub const SD_ACMD22_SEND_NUM_WR_BLOCKS: Command<CmdR1R6, SingleBlock> = Command {
     number: 22,
     response: CmdR1R6,
-    flag: NoFlag,
+    flag: SingleBlock,
 };
 
 //  ACMD23(ac, R1): Set the number of write blocks to be pre-erased before writing
@@ -0,0 +1,189 @@
+use bit_field::BitField;
+use embedded_error::mci::MciError;
+use embedded_error::ImplError;
+use embedded_hal::digital::v2::InputPin;
+
+use crate::bus::{Adtc, Bus, Read, SdMmcBus, Write, SD_MMC_BLOCK_SIZE};
+use crate::card::State;
+use crate::command_arguments::mmc::{BootMode, BootTiming, BusWidth, Partition};
+use crate::commands::{MMC_CMD0_BOOT_INITIATION, SDMMC_MCI_CMD0_GO_IDLE_STATE};
+use crate::mode_index::ModeIndex;
+
+use super::controller::Controller;
+use super::MAX_TRANSACTION_BLOCKS;
+
+// CMD0 arguments of the boot operation
+const MMC_CMD0_GO_PRE_IDLE_ARG: u32 = 0xF0F0_F0F0;
+const MMC_CMD0_BOOT_INITIATION_ARG: u32 = 0xFFFF_FFFA;
+
+// BOOT_WP bits
+const BOOT_WP_PWR_WP_EN: usize = 0;
+const BOOT_WP_PWR_WP_SEC_SEL: usize = 1;
+const BOOT_WP_PERM_WP_EN: usize = 2;
+const BOOT_WP_PERM_WP_SEC_SEL: usize = 3;
+const BOOT_WP_SEC_WP_SEL: usize = 7;
+
+impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT> {
+    /// CMD6 for MMC - Select the partition read by the boot operation with BOOT_CONFIG
+    /// `partition` is Boot1, Boot2 or User, None disables the boot. The card sends the boot
+    /// acknowledge before the data if `ack`.
+    /// self.card.ext_csd is updated
+    pub fn mmc_set_boot_config(
+        &mut self,
+        partition: Option<Partition>,
+        ack: bool,
+    ) -> Result<(), MciError> {
+        let enable = match partition {
+            None => 0,
+            Some(Partition::Boot1) => 1,
+            Some(Partition::Boot2) => 2,
+            Some(Partition::User) => 7,
+            Some(_) => return Err(MciError::Impl(ImplError::InvalidConfiguration)),
+        };
+        // The partition currently accessed is kept
+        let mut value = self.card.ext_csd.partition_config();
+        value.set_bits(3..6, enable).set_bit(6, ack);
+        self.mmc_switch(ModeIndex::BootConfig, value, 0)
+    }
+
+    /// CMD6 for MMC - Set the bus width and timing of the boot operation with
+    /// BOOT_BUS_CONDITIONS. The card goes back to 1 bit legacy bus after the boot unless
+    /// `retain`.
+    /// self.card.ext_csd is updated
+    pub fn mmc_set_boot_bus_conditions(
+        &mut self,
+        bus_width: BusWidth,
+        timing: BootTiming,
+        retain: bool,
+    ) -> Result<(), MciError> {
+        let bus_width = bus_width.sdr();
+        if timing == BootTiming::Ddr && bus_width == BusWidth::_1BIT {
+            return Err(MciError::Impl(ImplError::InvalidConfiguration));
+        }
+        let mut value = 0u8;
+        value
+            .set_bits(0..2, u32::from(&bus_width) as u8)
+            .set_bit(2, retain)
+            .set_bits(3..5, timing as u8);
+        self.mmc_switch(ModeIndex::BootBusWidth, value, 0)
+    }
+
+    /// CMD6 for MMC - Write protect the boot partitions with BOOT_WP
+    /// `partition` is Boot1 or Boot2, None protects both. Power-on protection is cleared by a
+    /// power cycle or a hardware reset, permanent protection can never be cleared.
+    /// self.card.ext_csd is updated
+    pub fn mmc_set_boot_write_protect(
+        &mut self,
+        partition: Option<Partition>,
+        permanent: bool,
+    ) -> Result<(), MciError> {
+        let (enable, select) = if permanent {
+            (BOOT_WP_PERM_WP_EN, BOOT_WP_PERM_WP_SEC_SEL)
+        } else {
+            (BOOT_WP_PWR_WP_EN, BOOT_WP_PWR_WP_SEC_SEL)
+        };
+        let mut value = 0u8;
+        value.set_bit(enable, true);
+        match partition {
+            None => {}
+            // Protection of a single boot partition is supported from eMMC 5.0
+            Some(p @ (Partition::Boot1 | Partition::Boot2))
+                if self.card.ext_csd.revision() >= 7 =>
+            {
+                value.set_bit(BOOT_WP_SEC_WP_SEL, true).set_bit(select, p == Partition::Boot2);
+            }
+            Some(_) => return Err(MciError::Impl(ImplError::InvalidConfiguration)),
+        }
+        self.mmc_switch(ModeIndex::BootWp, value, 0)
+    }
+}
+
+impl<BUS: SdMmcBus, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT> {
+    /// Read the boot partition into `destination` with the boot operation, without card
+    /// initialization. The bus is selected with self.card.clock and self.card.bus_width, which
+    /// shall match BOOT_BUS_CONDITIONS. `ack` shall match the BOOT_ACK setting.
+    /// The card is reset with CMD0 at the end, it shall then be initialized before any other
+    /// access.
+    /// self.card.state is updated
+    pub fn mmc_boot_read(
+        &mut self,
+        mode: BootMode,
+        ack: bool,
+        destination: &mut [u8],
+    ) -> Result<(), MciError> {
+        if destination.is_empty()
+            || !destination.len().is_multiple_of(SD_MMC_BLOCK_SIZE)
+            || destination.len() > MAX_TRANSACTION_BLOCKS * SD_MMC_BLOCK_SIZE
+        {
+            return Err(MciError::IncorrectDataSize);
+        }
+        let num_blocks = (destination.len() / SD_MMC_BLOCK_SIZE) as u16;
+        self.select()?;
+        // The boot operation is only accepted in pre-boot state
+        self.card
+            .bus
+            .send_command(SDMMC_MCI_CMD0_GO_IDLE_STATE.into(), MMC_CMD0_GO_PRE_IDLE_ARG)?;
+        self.card.state = State::Init;
+
+        let result = self.card.bus.boot_start(mode, ack, num_blocks).and_then(|_| match mode {
+            BootMode::Alternative => self.card.bus.adtc_start(
+                MMC_CMD0_BOOT_INITIATION.into(),
+                MMC_CMD0_BOOT_INITIATION_ARG,
+                SD_MMC_BLOCK_SIZE as u16,
+                num_blocks,
+                true,
+            ),
+            BootMode::CmdLineLow => Ok(()),
+        });
+        let result = result
+            .and_then(|_| self.card.bus.read_blocks(destination))
+            .and_then(|_| self.card.bus.wait_until_read_finished());
+        // Stopped and reset even if the read failed, the first error is returned
+        let stopped = self.card.bus.boot_stop(mode);
+        // CMD0 ends the boot operation
+        let reset = self.card.bus.send_command(SDMMC_MCI_CMD0_GO_IDLE_STATE.into(), 0);
+        result.and(stopped).and(reset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_error::mci::CommandOrDataError;
+
+    use crate::controller::mock::{self, MockBus};
+
+    use super::*;
+
+    #[test]
+    fn boot_read() {
+        let mut controller = mock::controller(MockBus::default(), true);
+        let mut destination = [0u8; 1024];
+        assert!(controller.mmc_boot_read(BootMode::Alternative, false, &mut destination).is_ok());
+        assert_eq!(destination, [0xA5; 1024]);
+        assert_eq!(
+            *controller.card.bus.commands.borrow(),
+            [(0, MMC_CMD0_GO_PRE_IDLE_ARG), (0, MMC_CMD0_BOOT_INITIATION_ARG), (0, 0)]
+        );
+        assert!(controller.card.state == State::Init);
+    }
+
+    #[test]
+    fn boot_read_error_resets_card() {
+        let bus = MockBus { fail_transfer: Some(0), ..Default::default() };
+        let mut controller = mock::controller(bus, true);
+        let mut destination = [0u8; 512];
+        assert!(matches!(
+            controller.mmc_boot_read(BootMode::Alternative, false, &mut destination),
+            Err(MciError::DataError(CommandOrDataError::Crc))
+        ));
+        assert_eq!(controller.card.bus.commands.borrow().last(), Some(&(0, 0)));
+
+        // The boot start error is returned once the card is reset
+        let mut controller = mock::controller(MockBus::default(), true);
+        assert!(matches!(
+            controller.mmc_boot_read(BootMode::CmdLineLow, false, &mut destination),
+            Err(MciError::Impl(ImplError::InvalidConfiguration))
+        ));
+        assert_eq!(controller.card.bus.indexes(), [0, 0]);
+    }
+}
@@ -0,0 +1,78 @@
+use embedded_error::mci::MciError;
+use embedded_error::ImplError;
+use embedded_hal::digital::v2::InputPin;
+
+use crate::bus::{Adtc, Bus, Read, Write};
+use crate::error::WriteBlocksError;
+use crate::mode_index::ModeIndex;
+
+use super::controller::Controller;
+
+// EXT_CSD gives no flush timeout, flushing a large cache may take seconds
+pub const MMC_FLUSH_TIMEOUT_MS: u32 = 30 * 1000;
+
+impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT> {
+    /// CMD6 for MMC - Enable or disable the volatile cache with CACHE_CTRL
+    /// Disabling the cache flushes it, waiting up to self.flush_timeout_ms
+    /// self.card.ext_csd is updated
+    pub fn mmc_set_cache(&mut self, enable: bool) -> Result<(), MciError> {
+        if self.card.ext_csd.cache_size() == 0 {
+            return Err(MciError::Impl(ImplError::InvalidConfiguration));
+        }
+        let timeout_ms = if enable { 0 } else { self.flush_timeout_ms };
+        self.mmc_switch(ModeIndex::CacheCtrl, enable as u8, timeout_ms)
+    }
+
+    /// CMD6 for MMC - Write the cached data to the non-volatile storage with FLUSH_CACHE
+    /// Nothing to do if the card has no cache enabled. Waits up to self.flush_timeout_ms.
+    pub fn flush(&mut self) -> Result<(), MciError> {
+        if !self.card.card_type.mmc() || !self.card.ext_csd.cache_enabled() {
+            return Ok(());
+        }
+        let result = self.mmc_switch(ModeIndex::FlushCache, 1, self.flush_timeout_ms);
+        // FLUSH_CACHE is cleared by the card at the end of the flush
+        self.card.ext_csd.set_byte(ModeIndex::FlushCache, 0);
+        result
+    }
+
+    /// Write blocks from `source` starting at `start` block address, then flush the cache
+    /// The data is persistent on return, the writes issued before are also flushed so it can
+    /// be used as a barrier. No block is reported as written if the flush fails.
+    pub fn write_blocks_fua(&mut self, start: u32, source: &[u8]) -> Result<(), WriteBlocksError> {
+        self.write_blocks(start, source)?;
+        self.flush().map_err(WriteBlocksError::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_error::mci::CommandOrDataError;
+
+    use crate::controller::mock::{self, MockBus};
+
+    use super::*;
+
+    #[test]
+    fn write_blocks_fua_flushes() {
+        let mut controller = mock::controller(MockBus::default(), true);
+        controller.card.ext_csd.set_byte(ModeIndex::CacheCtrl, 1);
+        assert!(controller.write_blocks_fua(8, &[0u8; 1024]).is_ok());
+        assert_eq!(controller.card.bus.indexes(), [25, 12, 6, 13]);
+    }
+
+    #[test]
+    fn write_blocks_fua_flush_error() {
+        let bus = MockBus { fail_command: Some(6), ..Default::default() };
+        let mut controller = mock::controller(bus, true);
+        controller.card.ext_csd.set_byte(ModeIndex::CacheCtrl, 1);
+        let result = controller.write_blocks_fua(8, &[0u8; 1024]);
+        assert!(matches!(
+            result,
+            Err(WriteBlocksError {
+                error: MciError::CommandError(CommandOrDataError::Timeout),
+                written: 0
+            })
+        ));
+        assert_eq!(controller.card.bus.writes[0].len(), 1024);
+    }
+}
@@ -5,6 +5,8 @@ use crate::bus::{Adtc, Bus, Read, Write};
 use crate::card::Card;
 use crate::registers::ocr::OcrRegister;
 
+use super::cache::MMC_FLUSH_TIMEOUT_MS;
+
 pub fn ocr_voltage_support() -> OcrRegister {
     let mut ocr = OcrRegister { val: 0 };
     ocr.set_vdd_27_28(true)
@@ -22,6 +24,15 @@ pub struct Controller<BUS, WP, DETECT> {
     pub write_protect_pin: WP,
     pub detect_pin: DETECT,
     pub lower_is_true: bool,
+    /// Send ACMD23 before SD multiple block writes so the card can pre-erase the blocks
+    pub pre_erase: bool,
+    /// Request eMMC reliable writes, only for predefined multiple block transfers
+    pub reliable_write: bool,
+    /// Enable the eMMC volatile cache at initialization, flush shall then be called to make
+    /// the written data persistent
+    pub cache: bool,
+    /// Timeout of the eMMC cache flush in milliseconds
+    pub flush_timeout_ms: u32,
 }
 
 impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT> {
@@ -33,7 +44,17 @@ impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<
         lower_is_true: bool,
         slot: u8,
     ) -> Self {
-        Controller { card, slot, write_protect_pin, detect_pin, lower_is_true }
+        Controller {
+            card,
+            slot,
+            write_protect_pin,
+            detect_pin,
+            lower_is_true,
+            pre_erase: false,
+            reliable_write: false,
+            cache: false,
+            flush_timeout_ms: MMC_FLUSH_TIMEOUT_MS,
+        }
     }
 
     pub fn write_protected(&self) -> Result<bool, MciError> {
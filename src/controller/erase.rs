@@ -0,0 +1,264 @@
+use embedded_error::mci::MciError;
+use embedded_error::ImplError;
+use embedded_hal::digital::v2::InputPin;
+
+use crate::bus::{Adtc, Bus, Read, Write};
+use crate::command_arguments::cmd38::{Cmd38, EraseMode};
+use crate::commands::{
+    MMC_CMD35_ERASE_GROUP_START, MMC_CMD36_ERASE_GROUP_END, SDMMC_CMD38_ERASE,
+    SD_CMD32_ERASE_WR_BLK_START, SD_CMD33_ERASE_WR_BLK_END,
+};
+use crate::mode_index::ModeIndex;
+use crate::registers::sd::sd_status::SdStatusRegister;
+
+use super::controller::Controller;
+
+// Erase timeout per erase group when not specified by the card
+pub const SD_ERASE_TIMEOUT_MS: u32 = 250;
+pub const MMC_ERASE_TIMEOUT_MS: u32 = 300;
+
+impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT> {
+    /// Erase group size in blocks, erase start and end shall be aligned on it
+    /// High capacity erase groups of 512 KBytes units are used by MMC with ERASE_GROUP_DEF set
+    pub fn erase_group_size(&self) -> u32 {
+        let csd = &self.card.csd;
+        if self.card.card_type.mmc() && self.card.ext_csd.erase_group_def() {
+            self.card.ext_csd.hc_erase_group_size() as u32 * 1024
+        } else if self.card.card_type.mmc() {
+            (csd.mmc_erase_group_size() as u32 + 1) * (csd.mmc_erase_group_multiplier() as u32 + 1)
+        } else if self.card.card_type.high_capacity() || csd.sd_erase_block_enable() {
+            1
+        } else {
+            csd.sd_sector_size() as u32 + 1
+        }
+    }
+
+    /// Erase `num_blocks` blocks starting at `start` block address
+    /// Erase mode requires the range to be aligned on the erase group size, trim and discard are
+    /// write block granular. The busy period is waited out before returning.
+    pub fn erase(&mut self, start: u32, num_blocks: u32, mode: EraseMode) -> Result<(), MciError> {
+        let arg = if self.card.card_type.mmc() { Cmd38::mmc(mode) } else { Cmd38::sd(mode) };
+        let arg = arg.ok_or(MciError::Impl(ImplError::InvalidConfiguration))?;
+        if self.card.card_type.mmc() && !self.mmc_erase_supported(mode) {
+            return Err(MciError::Impl(ImplError::InvalidConfiguration));
+        }
+        if num_blocks == 0 {
+            return Err(MciError::IncorrectDataSize);
+        }
+        let end = start
+            .checked_add(num_blocks - 1)
+            .ok_or(MciError::Impl(ImplError::InvalidConfiguration))?;
+        let group_size = self.erase_group_size();
+        if group_size == 0 {
+            return Err(MciError::Impl(ImplError::InvalidConfiguration));
+        }
+        let alignment = match mode {
+            EraseMode::Erase | EraseMode::SecureErase => group_size,
+            _ => 1,
+        };
+        if !start.is_multiple_of(alignment) || !num_blocks.is_multiple_of(alignment) {
+            return Err(MciError::Impl(ImplError::InvalidConfiguration));
+        }
+
+        self.select()?;
+        if self.write_protected()? {
+            return Err(MciError::WriteProtected);
+        }
+        self.load_status()?;
+        // SD Status shall be read before the erase sequence
+        let sd_status = if self.card.card_type.sd() { self.sd_status().ok() } else { None };
+        if self.card.card_type.sd() && !sd_erase_supported(sd_status.as_ref(), mode) {
+            return Err(MciError::Impl(ImplError::InvalidConfiguration));
+        }
+        let sd_timeout = match (&sd_status, mode) {
+            (Some(status), EraseMode::Erase | EraseMode::Fule) => {
+                self.sd_erase_timeout(status, mode, num_blocks)
+            }
+            _ => None,
+        };
+
+        let first = self.block_address(start);
+        let last = self.block_address(end);
+        let timeout = if self.card.card_type.mmc() {
+            self.card.bus.send_command(MMC_CMD35_ERASE_GROUP_START.into(), first)?;
+            self.card.bus.send_command(MMC_CMD36_ERASE_GROUP_END.into(), last)?;
+            self.mmc_erase_timeout(mode)
+        } else {
+            self.card.bus.send_command(SD_CMD32_ERASE_WR_BLK_START.into(), first)?;
+            self.card.bus.send_command(SD_CMD33_ERASE_WR_BLK_END.into(), last)?;
+            SD_ERASE_TIMEOUT_MS
+        };
+        self.card.bus.send_command(SDMMC_CMD38_ERASE.into(), arg.val)?;
+
+        // Erase timeout is proportional to the number of erase groups covered by the range
+        let groups = match mode {
+            // The address range is ignored, the whole user area is erased
+            EraseMode::Fule => {
+                (self.card.capacity as u64 * 2 / group_size as u64).min(u32::MAX as u64) as u32
+            }
+            _ => end / group_size - start / group_size + 1,
+        };
+        let timeout = sd_timeout.unwrap_or(timeout.saturating_mul(groups));
+        let status = self.wait_ready(timeout.max(1000))?;
+        if status.write_protect_erase_skip() {
+            return Err(MciError::WriteProtected);
+        }
+        if status.erase_sequence_error() || status.erase_parameter() {
+            return Err(MciError::WriteError);
+        }
+        Ok(())
+    }
+
+    /// CMD6 for MMC - Purge the unmapped memory with SANITIZE_START
+    /// CMD13 is polled up to `timeout_ms` milliseconds for the end of the operation, which may
+    /// take minutes.
+    pub fn mmc_sanitize(&mut self, timeout_ms: u32) -> Result<(), MciError> {
+        if !self.card.card_type.mmc() || !self.card.ext_csd.sanitize_supported() {
+            return Err(MciError::Impl(ImplError::InvalidConfiguration));
+        }
+        self.select()?;
+        if self.write_protected()? {
+            return Err(MciError::WriteProtected);
+        }
+        self.load_status()?;
+        let result = self.mmc_switch(ModeIndex::Sanitize, 1, timeout_ms.max(1));
+        // SANITIZE_START is cleared by the card at the end of the operation
+        self.card.ext_csd.set_byte(ModeIndex::Sanitize, 0);
+        result
+    }
+
+    /// Erase timeout in milliseconds from the SD Status, computed from the number of AUs to
+    /// erase. None if the card does not specify it.
+    fn sd_erase_timeout(
+        &self,
+        status: &SdStatusRegister,
+        mode: EraseMode,
+        num_blocks: u32,
+    ) -> Option<u32> {
+        let au_kbytes = status.au_size_kbytes()?;
+        if status.erase_size() == 0 || status.erase_timeout() == 0 {
+            return None;
+        }
+        let kbytes =
+            if mode == EraseMode::Fule { self.card.capacity } else { num_blocks.div_ceil(2) };
+        let aus = kbytes.div_ceil(au_kbytes) as u64;
+        let seconds = status.erase_timeout() as u64 * aus / status.erase_size() as u64
+            + status.erase_offset() as u64;
+        Some((seconds * 1000).min(u32::MAX as u64) as u32)
+    }
+
+    /// Whether the MMC supports the erase `mode`, trim and discard are handled as erase by the
+    /// devices not supporting them
+    fn mmc_erase_supported(&self, mode: EraseMode) -> bool {
+        let ext_csd = &self.card.ext_csd;
+        match mode {
+            EraseMode::Trim => ext_csd.trim_supported(),
+            EraseMode::Discard => ext_csd.discard_supported(),
+            _ if mode.secure() => ext_csd.secure_purge_supported(),
+            _ => true,
+        }
+    }
+
+    /// Timeout per erase group of erase_group_size() in milliseconds from EXT_CSD, defaults to
+    /// MMC_ERASE_TIMEOUT_MS
+    fn mmc_erase_timeout(&self, mode: EraseMode) -> u32 {
+        let ext_csd = &self.card.ext_csd;
+        let timeout = match mode {
+            // High capacity erase timeout only applies to high capacity erase groups
+            EraseMode::Erase if ext_csd.erase_group_def() => ext_csd.erase_timeout(),
+            EraseMode::Trim | EraseMode::Discard => ext_csd.trim_timeout(),
+            EraseMode::SecureErase => ext_csd.secure_erase_timeout(),
+            EraseMode::SecureTrim1 | EraseMode::SecureTrim2 => ext_csd.secure_trim_timeout(),
+            _ => 0,
+        };
+        if timeout == 0 {
+            MMC_ERASE_TIMEOUT_MS
+        } else {
+            timeout
+        }
+    }
+}
+
+/// Whether the SD card supports the erase `mode`, from its SD Status
+fn sd_erase_supported(status: Option<&SdStatusRegister>, mode: EraseMode) -> bool {
+    match mode {
+        EraseMode::Discard => status.is_some_and(|status| status.discard_support()),
+        EraseMode::Fule => status.is_some_and(|status| status.fule_support()),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use crate::controller::mock::{self, MockBus};
+    use crate::registers::ext_csd::{
+        EXT_CSD_HC_ERASE_GRP_SIZE_INDEX, EXT_CSD_REV_INDEX, EXT_CSD_SEC_FEATURE_SUPPORT_INDEX,
+    };
+
+    use super::*;
+
+    #[test]
+    fn mmc_high_capacity_erase_groups() {
+        let mut controller = mock::controller(MockBus::default(), true);
+        controller.card.ext_csd.set_byte(ModeIndex::EraseGroupDef, 1);
+        controller.card.ext_csd.0[EXT_CSD_HC_ERASE_GRP_SIZE_INDEX] = 2;
+        assert_eq!(controller.erase_group_size(), 2048);
+        assert!(matches!(
+            controller.erase(1024, 2048, EraseMode::Erase),
+            Err(MciError::Impl(ImplError::InvalidConfiguration))
+        ));
+        assert!(controller.erase(2048, 4096, EraseMode::Erase).is_ok());
+        assert_eq!(
+            *controller.card.bus.commands.borrow(),
+            [(13, 1 << 16), (35, 2048), (36, 6143), (38, 0), (13, 1 << 16)]
+        );
+    }
+
+    #[test]
+    fn mmc_trim_and_discard_support() {
+        let mut controller = mock::controller(MockBus::default(), true);
+        for mode in [EraseMode::Trim, EraseMode::Discard, EraseMode::SecureTrim1] {
+            assert!(matches!(
+                controller.erase(0, 1, mode),
+                Err(MciError::Impl(ImplError::InvalidConfiguration))
+            ));
+        }
+        assert!(controller.card.bus.commands.borrow().is_empty());
+        controller.card.ext_csd.0[EXT_CSD_SEC_FEATURE_SUPPORT_INDEX] = 0x10;
+        assert!(controller.erase(3, 1, EraseMode::Trim).is_ok());
+        assert_eq!(controller.card.bus.commands.borrow()[3], (38, 1));
+        controller.card.ext_csd.0[EXT_CSD_REV_INDEX] = 6;
+        assert!(controller.erase(3, 1, EraseMode::Discard).is_ok());
+    }
+
+    #[test]
+    fn sd_discard_and_fule_support() {
+        let mut status = std::vec![0u8; 64];
+        let bus = MockBus { reads: [status.clone()].into(), ..MockBus::default() };
+        let mut controller = mock::controller(bus, false);
+        assert!(matches!(
+            controller.erase(0, 1, EraseMode::Discard),
+            Err(MciError::Impl(ImplError::InvalidConfiguration))
+        ));
+        assert_eq!(controller.card.bus.indexes(), [13, 55, 13]);
+
+        // DISCARD_SUPPORT and FULE_SUPPORT
+        status[24] = 0x03;
+        let bus = MockBus { reads: [status.clone(), status].into(), ..MockBus::default() };
+        let mut controller = mock::controller(bus, false);
+        assert!(controller.erase(5, 2, EraseMode::Discard).is_ok());
+        assert!(controller.erase(0, 1, EraseMode::Fule).is_ok());
+        let commands = controller.card.bus.commands.borrow();
+        assert_eq!(commands[3..6], [(32, 5), (33, 6), (38, 1)]);
+        assert_eq!(commands[10..13], [(32, 0), (33, 0), (38, 2)]);
+    }
+
+    #[test]
+    fn mmc_legacy_erase_groups() {
+        let controller = mock::controller(MockBus::default(), true);
+        // ERASE_GRP_SIZE and ERASE_GRP_MULT are 0 in the default CSD
+        assert_eq!(controller.erase_group_size(), 1);
+    }
+}
@@ -0,0 +1,92 @@
+use embedded_error::mci::MciError;
+use embedded_error::ImplError;
+use embedded_hal::digital::v2::InputPin;
+
+use crate::bus::{Adtc, Bus, Read, Write, SD_MMC_BLOCK_SIZE};
+use crate::card::State;
+use crate::command_arguments::cmd42::Cmd42;
+use crate::commands::{SDMMC_CMD16_SET_BLOCKLEN, SDMMC_CMD42_LOCK_UNLOCK};
+
+use super::controller::Controller;
+
+// Forced erase shall be completed within 3 minutes
+pub const FORCED_ERASE_TIMEOUT_MS: u32 = 3 * 60 * 1000;
+
+impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT> {
+    /// CMD13 - Whether the card is password locked
+    /// self.card.state is updated
+    pub fn is_locked(&mut self) -> Result<bool, MciError> {
+        let locked = self.load_status()?.card_is_locked();
+        self.update_lock_state(locked);
+        Ok(locked)
+    }
+
+    /// Set a new password, `old` is empty if no password is set
+    pub fn set_password(&mut self, old: &[u8], new: &[u8]) -> Result<(), MciError> {
+        let data = Cmd42::set_password(old, new).ok_or(MciError::IncorrectDataSize)?;
+        self.lock_unlock(&data, 0)
+    }
+
+    pub fn clear_password(&mut self, password: &[u8]) -> Result<(), MciError> {
+        let data = Cmd42::clear_password(password).ok_or(MciError::IncorrectDataSize)?;
+        self.lock_unlock(&data, 0)
+    }
+
+    pub fn lock(&mut self, password: &[u8]) -> Result<(), MciError> {
+        let data = Cmd42::lock(password).ok_or(MciError::IncorrectDataSize)?;
+        self.lock_unlock(&data, 0)
+    }
+
+    /// Unlock the card, a card locked at initialization shall be initialized again afterward
+    pub fn unlock(&mut self, password: &[u8]) -> Result<(), MciError> {
+        let data = Cmd42::unlock(password).ok_or(MciError::IncorrectDataSize)?;
+        self.lock_unlock(&data, 0)
+    }
+
+    /// Erase all the card content, password included
+    pub fn forced_erase(&mut self) -> Result<(), MciError> {
+        self.lock_unlock(&Cmd42::forced_erase(), FORCED_ERASE_TIMEOUT_MS)
+    }
+
+    /// CMD42 - Send the lock card data structure
+    /// Waits up to `timeout_ms` milliseconds for the end of busy, load_status timeout if 0
+    fn lock_unlock(&mut self, data: &Cmd42, timeout_ms: u32) -> Result<(), MciError> {
+        self.select()?;
+        self.load_status()?;
+        let bytes = data.as_bytes();
+        // The block length is the size of the lock card data structure
+        self.card.bus.send_command(SDMMC_CMD16_SET_BLOCKLEN.into(), bytes.len() as u32)?;
+        let result = self.card.bus.adtc_start(
+            SDMMC_CMD42_LOCK_UNLOCK.into(),
+            0,
+            bytes.len() as u16,
+            1,
+            true,
+        );
+        let result = result
+            .and_then(|_| self.card.bus.write_blocks(bytes))
+            .and_then(|_| self.card.bus.wait_until_write_finished());
+        let status = result.and_then(|_| {
+            if timeout_ms > 0 {
+                self.wait_ready(timeout_ms)
+            } else {
+                self.load_status()
+            }
+        });
+        self.card.bus.send_command(SDMMC_CMD16_SET_BLOCKLEN.into(), SD_MMC_BLOCK_SIZE as u32)?;
+        let status = status?;
+        self.update_lock_state(status.card_is_locked());
+        if status.unlock_failed() {
+            return Err(MciError::Impl(ImplError::PermissionDenied));
+        }
+        Ok(())
+    }
+
+    fn update_lock_state(&mut self, locked: bool) {
+        if locked {
+            self.card.state = State::Locked;
+        } else if self.card.state == State::Locked {
+            self.card.state = State::Ready;
+        }
+    }
+}
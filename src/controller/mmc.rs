@@ -0,0 +1,45 @@
+use embedded_error::mci::MciError;
+use embedded_error::ImplError;
+use embedded_hal::digital::v2::InputPin;
+
+use crate::bus::{Adtc, Bus, Read, Write};
+use crate::command_arguments::mmc::{Access, Cmd6};
+use crate::commands::MMC_CMD6_SWITCH;
+use crate::mode_index::ModeIndex;
+use crate::registers::sd::card_status::CardStatusRegister;
+
+use super::controller::Controller;
+
+// CMD6 timeout when GENERIC_CMD6_TIME is not specified by the card
+pub const MMC_SWITCH_TIMEOUT_MS: u32 = 500;
+
+impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT> {
+    /// CMD6 for MMC - Write a byte of EXT_CSD
+    /// Waits up to `timeout_ms` milliseconds for the end of busy, GENERIC_CMD6_TIME if 0
+    /// self.card.ext_csd is updated
+    pub fn mmc_switch(
+        &mut self,
+        index: ModeIndex,
+        value: u8,
+        timeout_ms: u32,
+    ) -> Result<(), MciError> {
+        if !self.card.card_type.mmc() {
+            return Err(MciError::Impl(ImplError::InvalidConfiguration));
+        }
+        let timeout_ms = match (timeout_ms, self.card.ext_csd.generic_cmd6_time()) {
+            (0, 0) => MMC_SWITCH_TIMEOUT_MS,
+            (0, generic) => generic,
+            (timeout, _) => timeout,
+        };
+        let mut arg = Cmd6::default();
+        arg.set_access(Access::WriteByte).set_mode_index(index).set_value(value);
+        self.card.bus.send_command(MMC_CMD6_SWITCH.into(), arg.val)?;
+        let response = CardStatusRegister { val: self.card.bus.get_response()? };
+        let status = self.wait_ready(timeout_ms)?;
+        if response.switch_error() || status.switch_error() {
+            return Err(MciError::Impl(ImplError::InvalidConfiguration));
+        }
+        self.card.ext_csd.set_byte(index, value);
+        Ok(())
+    }
+}
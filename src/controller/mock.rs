@@ -0,0 +1,225 @@
+extern crate std;
+
+use core::cell::RefCell;
+use std::collections::VecDeque;
+use std::vec::Vec;
+
+use embedded_error::mci::{CommandOrDataError, MciError};
+use embedded_error::ImplError;
+
+use crate::bus::{Adtc, Bus, Read, SdMmcBus, SignalVoltage, Timing, Write};
+use crate::card::card::Type;
+use crate::card::version::CardVersion;
+use crate::card::{Card, State};
+use crate::command_arguments::mmc::BusWidth;
+use crate::dummy_input_pin::DummyInputPin;
+
+use super::controller::Controller;
+
+// Ready for data in transfer state
+const READY_STATUS: u32 = 0x900;
+const ACMD22_INDEX: u8 = 22;
+
+/// Bus recording the commands sent to a card which is always ready
+#[derive(Default)]
+pub struct MockBus {
+    /// Index and argument of the commands sent, stop commands included
+    pub commands: RefCell<Vec<(u8, u32)>>,
+    /// Data of the following block reads, blocks are filled with 0xA5 once empty
+    pub reads: VecDeque<Vec<u8>>,
+    /// Data of the block writes
+    pub writes: Vec<Vec<u8>>,
+    /// Index of the block transfer failing with a CRC error
+    pub fail_transfer: Option<usize>,
+    /// Index of the command failing with a timeout
+    pub fail_command: Option<u8>,
+    /// Number of blocks written reported by ACMD22
+    pub written_blocks: u32,
+    /// Number of block transfers done
+    pub transfers: usize,
+    /// Timings supported by the host, other timings are rejected by set_timing
+    pub timings: Vec<Timing>,
+    /// Last signal voltage applied
+    pub signal_voltage: Option<SignalVoltage>,
+    /// Number of sampling points, setting a sampling point always fails
+    pub tuning_points: u8,
+}
+
+impl MockBus {
+    /// Index of the commands sent
+    pub fn indexes(&self) -> Vec<u8> {
+        self.commands.borrow().iter().map(|&(index, _)| index).collect()
+    }
+
+    fn transfer(&mut self) -> Result<(), MciError> {
+        let failed = self.fail_transfer == Some(self.transfers);
+        self.transfers += 1;
+        if failed {
+            Err(MciError::DataError(CommandOrDataError::Crc))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn last_index(&self) -> Option<u8> {
+        self.commands.borrow().last().map(|&(index, _)| index)
+    }
+}
+
+impl Bus for MockBus {
+    fn init(&mut self) -> Result<(), MciError> {
+        Ok(())
+    }
+
+    fn deinit(&mut self) -> Result<(), MciError> {
+        Ok(())
+    }
+
+    fn select_device(
+        &mut self,
+        _slot: u8,
+        _clock: u32,
+        _bus_width: &BusWidth,
+        _high_speed: bool,
+    ) -> Result<(), MciError> {
+        Ok(())
+    }
+
+    fn deselect_device(&mut self, _slot: u8) -> Result<(), MciError> {
+        Ok(())
+    }
+
+    fn send_clock(&mut self) -> Result<(), MciError> {
+        Ok(())
+    }
+
+    fn send_command(&mut self, cmd: u32, arg: u32) -> Result<(), MciError> {
+        let index = (cmd & 0x3F) as u8;
+        self.commands.borrow_mut().push((index, arg));
+        if self.fail_command == Some(index) {
+            return Err(MciError::CommandError(CommandOrDataError::Timeout));
+        }
+        Ok(())
+    }
+
+    fn get_response(&mut self) -> Result<u32, MciError> {
+        Ok(READY_STATUS)
+    }
+}
+
+impl Adtc for MockBus {
+    fn adtc_start(
+        &mut self,
+        command: u32,
+        argument: u32,
+        _block_size: u16,
+        _block_amount: u16,
+        _access_in_blocks: bool,
+    ) -> Result<(), MciError> {
+        self.send_command(command, argument)
+    }
+
+    fn adtc_stop(&self, command: u32, argument: u32) -> Result<(), MciError> {
+        self.commands.borrow_mut().push(((command & 0x3F) as u8, argument));
+        Ok(())
+    }
+}
+
+impl Read for MockBus {
+    fn read_word(&mut self) -> Result<u32, MciError> {
+        Ok(0)
+    }
+
+    fn read_blocks(&mut self, blocks: &mut [u8]) -> Result<(), MciError> {
+        if self.last_index() == Some(ACMD22_INDEX) {
+            blocks.copy_from_slice(&self.written_blocks.to_be_bytes());
+            return Ok(());
+        }
+        self.transfer()?;
+        match self.reads.pop_front() {
+            Some(data) => blocks.copy_from_slice(&data),
+            None => blocks.fill(0xA5),
+        }
+        Ok(())
+    }
+
+    fn wait_until_read_finished(&mut self) -> Result<(), MciError> {
+        Ok(())
+    }
+}
+
+impl Write for MockBus {
+    fn write_word(&mut self, _val: u32) -> Result<(), MciError> {
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, blocks: &[u8]) -> Result<(), MciError> {
+        self.transfer()?;
+        self.writes.push(blocks.to_vec());
+        Ok(())
+    }
+
+    fn wait_until_write_finished(&mut self) -> Result<(), MciError> {
+        Ok(())
+    }
+}
+
+impl SdMmcBus for MockBus {
+    fn get_bus_width(&mut self, _slot: u8) -> Result<BusWidth, MciError> {
+        Ok(BusWidth::_4BIT)
+    }
+
+    fn is_high_speed_capable(&mut self) -> Result<bool, MciError> {
+        Ok(true)
+    }
+
+    fn get_response128(&mut self) -> Result<[u32; 4], MciError> {
+        Ok([0; 4])
+    }
+
+    fn is_timing_supported(&mut self, timing: Timing) -> Result<bool, MciError> {
+        Ok(self.timings.contains(&timing))
+    }
+
+    fn set_signal_voltage(&mut self, voltage: SignalVoltage) -> Result<(), MciError> {
+        self.signal_voltage = Some(voltage);
+        Ok(())
+    }
+
+    fn set_timing(&mut self, timing: Timing) -> Result<(), MciError> {
+        if self.timings.contains(&timing) {
+            Ok(())
+        } else {
+            Err(MciError::Impl(ImplError::InvalidConfiguration))
+        }
+    }
+
+    fn tuning_points(&mut self) -> u8 {
+        self.tuning_points
+    }
+}
+
+/// Controller of an initialized high capacity card, not write protected
+pub fn controller(bus: MockBus, mmc: bool) -> Controller<MockBus, DummyInputPin, DummyInputPin> {
+    let mut card_type = Type::default();
+    card_type.set_sd(!mmc).set_mmc(mmc).set_high_capacity(true);
+    let card = Card {
+        bus,
+        clock: 25_000_000,
+        capacity: 32 * 1024 * 1024,
+        rca: 1,
+        state: State::Ready,
+        card_type,
+        version: CardVersion::Unknown,
+        bus_width: BusWidth::_4BIT,
+        cid: Default::default(),
+        csd: Default::default(),
+        ext_csd: Default::default(),
+        high_speed: false,
+        timing: Timing::Legacy,
+        signal_voltage: SignalVoltage::_3V3,
+        set_block_count: false,
+    };
+    let pin = || DummyInputPin { high: true };
+    Controller::new(card, pin(), pin(), true, 0)
+}
@@ -1,31 +1,63 @@
+mod boot;
+mod cache;
 mod controller;
+mod erase;
+mod lock;
+mod mmc;
+#[cfg(test)]
+pub(crate) mod mock;
+mod partition;
+mod power;
+mod rpmb;
 mod sdcard;
 mod sdmmc;
+mod spi;
+mod timing;
+mod uhs;
+mod write_protect;
 
+use bit_field::BitField;
 use embedded_error::mci::MciError;
 use embedded_error::mci::MciError::UnusableCard;
 use embedded_error::ImplError;
 use embedded_hal::digital::v2::InputPin;
 
-use crate::bus::{Adtc, Bus, Read, Write, SD_MMC_BLOCK_SIZE};
+use crate::bus::{Adtc, Bus, Read, Timing, Write, SD_MMC_BLOCK_SIZE};
 use crate::card::State;
 use crate::command_arguments::mmc::BusWidth;
 use crate::commands::{
-    SDMMC_CMD12_STOP_TRANSMISSION, SDMMC_CMD17_READ_SINGLE_BLOCK, SDMMC_CMD18_READ_MULTIPLE_BLOCK,
-    SDMMC_CMD24_WRITE_BLOCK, SDMMC_CMD25_WRITE_MULTIPLE_BLOCK, SDMMC_MCI_CMD13_SEND_STATUS,
+    MMC_CMD23_SET_BLOCK_COUNT, SDMMC_CMD12_STOP_TRANSMISSION, SDMMC_CMD17_READ_SINGLE_BLOCK,
+    SDMMC_CMD18_READ_MULTIPLE_BLOCK, SDMMC_CMD24_WRITE_BLOCK, SDMMC_CMD25_WRITE_MULTIPLE_BLOCK,
+    SDMMC_CMD55_APP_CMD, SDMMC_MCI_CMD13_SEND_STATUS, SD_ACMD23_SET_WR_BLK_ERASE_COUNT,
 };
+use crate::error::WriteBlocksError;
 use crate::registers::sd::card_status::CardStatusRegister;
 use crate::transaction::Transaction;
 
 pub use controller::Controller;
 
+/// Maximum number of blocks of a single transaction
+pub const MAX_TRANSACTION_BLOCKS: usize = u16::MAX as usize;
+
 impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT> {
     /// CMD13: Get status register.
     /// Waits for the clear of the busy flag
     pub fn load_status(&mut self) -> Result<CardStatusRegister, MciError> {
-        let mut status = CardStatusRegister::default();
         // TODO maybe proper timeout
-        for i in (0..200_000u32).rev() {
+        self.poll_status(200_000)
+    }
+
+    /// CMD13: Get status register.
+    /// Waits up to `timeout_ms` milliseconds for the clear of the busy flag
+    pub fn wait_ready(&mut self, timeout_ms: u32) -> Result<CardStatusRegister, MciError> {
+        // One poll is (6+6)*8 cycles, the last retry only reports the timeout
+        let retries = timeout_ms as u64 * self.card.clock as u64 / 1000 / ((6 + 6) * 8) + 2;
+        self.poll_status(retries.min(u32::MAX as u64) as u32)
+    }
+
+    fn poll_status(&mut self, retries: u32) -> Result<CardStatusRegister, MciError> {
+        let mut status = CardStatusRegister::default();
+        for i in (0..retries).rev() {
             if i == 0 {
                 return Err(MciError::Impl(ImplError::TimedOut));
             }
@@ -73,6 +105,7 @@ impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<
             self.card.clock = 400_000;
             self.card.bus_width = BusWidth::_1BIT;
             self.card.high_speed = false;
+            self.card.timing = Timing::Legacy;
         }
         if self.card.state == State::Unusable {
             return Err(UnusableCard);
@@ -93,20 +126,16 @@ impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<
         self.select()?;
         // Wait for data status
         self.load_status()?;
+        self.check_partition_range(start, num_blocks as u32)?;
+        let predefined = self.set_block_count(num_blocks, false)?;
         let cmd: u32 = if num_blocks > 1 {
             SDMMC_CMD18_READ_MULTIPLE_BLOCK.into()
         } else {
             SDMMC_CMD17_READ_SINGLE_BLOCK.into()
         };
-
-        // SDSC Card (CCS=0) uses byte unit address,
-        // SDHC and SDXC Cards (CCS=1) use block unit address (512 Bytes unit).
-        let mut arg = start;
-        if !self.card.card_type.high_capacity() {
-            arg = start * SD_MMC_BLOCK_SIZE as u32;
-        }
+        let arg = self.block_address(start);
         self.card.bus.adtc_start(cmd, arg, SD_MMC_BLOCK_SIZE as u16, num_blocks, true)?;
-        Ok(Transaction::new(num_blocks))
+        Ok(Transaction { predefined, ..Transaction::new(num_blocks) })
     }
 
     pub fn start_read(
@@ -135,7 +164,7 @@ impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<
         }
 
         // All blocks are transferred then stop read operation
-        if transaction.remain == 1 {
+        if transaction.total == 1 || (transaction.predefined && !abort) {
             return Ok(());
         }
 
@@ -159,19 +188,26 @@ impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<
             return Err(MciError::WriteProtected); // TODO proper write protection error
         }
 
+        if num_blocks > 1 && self.pre_erase && self.card.card_type.sd() {
+            // Number of blocks to be pre-erased, the content of the blocks not written
+            // afterward is undefined
+            let rca = (self.card.rca as u32) << 16;
+            self.card.bus.send_command(SDMMC_CMD55_APP_CMD.into(), rca)?;
+            self.card
+                .bus
+                .send_command(SD_ACMD23_SET_WR_BLK_ERASE_COUNT.into(), num_blocks as u32)?;
+        }
+
+        self.check_partition_range(start, num_blocks as u32)?;
+        let reliable = self.reliable_write && self.card.card_type.mmc();
+        let predefined = self.set_block_count(num_blocks, reliable)?;
         let cmd: u32 = if num_blocks > 1 {
             SDMMC_CMD25_WRITE_MULTIPLE_BLOCK.into()
         } else {
             SDMMC_CMD24_WRITE_BLOCK.into()
         };
 
-        // SDSC Card (CCS=0) uses byte unit address,
-        // SDHC and SDXC Cards (CCS=1) use block unit address (512 Bytes unit).
-        let mut arg = start;
-        if !self.card.card_type.high_capacity() {
-            arg = start * SD_MMC_BLOCK_SIZE as u32;
-        }
-
+        let arg = self.block_address(start);
         self.card.bus.adtc_start(cmd, arg, SD_MMC_BLOCK_SIZE as u16, num_blocks, true)?; // TODO proper error
 
         let resp = CardStatusRegister { val: self.card.bus.get_response()? };
@@ -179,7 +215,7 @@ impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<
             return Err(MciError::WriteProtected);
         }
 
-        Ok(Transaction { total: num_blocks, remain: num_blocks })
+        Ok(Transaction { predefined, ..Transaction::new(num_blocks) })
     }
 
     pub fn start_write_blocks(
@@ -208,8 +244,8 @@ impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<
         }
 
         // All blocks are transferred then stop write operation
-        if transaction.remain == 1 {
-            // Single block transfer, then nothing to do
+        if transaction.total == 1 || (transaction.predefined && !abort) {
+            // Single block or predefined transfer, then nothing to do
             return Ok(()); // TODO proper return?
         }
 
@@ -217,4 +253,274 @@ impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<
         self.card.bus.adtc_stop(SDMMC_CMD12_STOP_TRANSMISSION.into(), 0)?;
         Ok(())
     }
+
+    /// Read blocks starting at `start` block address into `destination`
+    /// Single or multiple block read is selected from the destination size, which must be a
+    /// multiple of SD_MMC_BLOCK_SIZE. Large reads are split into several transactions.
+    pub fn read_blocks(&mut self, start: u32, destination: &mut [u8]) -> Result<(), MciError> {
+        if destination.is_empty() || !destination.len().is_multiple_of(SD_MMC_BLOCK_SIZE) {
+            return Err(MciError::IncorrectDataSize);
+        }
+        let num_blocks = destination.len() / SD_MMC_BLOCK_SIZE;
+        let segments = destination.chunks_mut(MAX_TRANSACTION_BLOCKS * SD_MMC_BLOCK_SIZE);
+        self.read_segments(start, num_blocks, segments)
+    }
+
+    /// Read `num_blocks` blocks starting at `start` block address into `segments`
+    /// The segment sizes are multiples of SD_MMC_BLOCK_SIZE and the segments do not cross the
+    /// boundaries of the MAX_TRANSACTION_BLOCKS blocks transactions
+    pub(crate) fn read_segments<'a>(
+        &mut self,
+        start: u32,
+        num_blocks: usize,
+        mut segments: impl Iterator<Item = &'a mut [u8]>,
+    ) -> Result<(), MciError> {
+        let mut address = start;
+        for count in transaction_sizes(num_blocks) {
+            let mut transaction = self.init_read_blocks(address, count)?;
+            let mut result = Ok(());
+            while result.is_ok() && transaction.remain > 0 {
+                result = match segments.next() {
+                    Some(segment) => self.start_read(&mut transaction, segment),
+                    None => Err(MciError::IncorrectDataSize),
+                };
+            }
+            let result = result.and_then(|_| self.wait_end_of_read_blocks(false, &mut transaction));
+            if result.is_err() {
+                self.abort_transaction(&transaction);
+            }
+            result?;
+            address += count as u32;
+        }
+        Ok(())
+    }
+
+    /// Write blocks from `source` starting at `start` block address
+    /// Single or multiple block write is selected from the source size, which must be a
+    /// multiple of SD_MMC_BLOCK_SIZE. Large writes are split into several transactions.
+    /// On failure the error holds the number of blocks written, from ACMD22 for SD cards.
+    pub fn write_blocks(&mut self, start: u32, source: &[u8]) -> Result<(), WriteBlocksError> {
+        if source.is_empty() || !source.len().is_multiple_of(SD_MMC_BLOCK_SIZE) {
+            return Err(MciError::IncorrectDataSize.into());
+        }
+        let num_blocks = source.len() / SD_MMC_BLOCK_SIZE;
+        let segments = source.chunks(MAX_TRANSACTION_BLOCKS * SD_MMC_BLOCK_SIZE);
+        self.write_segments(start, num_blocks, segments)
+    }
+
+    /// Write `num_blocks` blocks from `segments` starting at `start` block address
+    /// The segment sizes are multiples of SD_MMC_BLOCK_SIZE and the segments do not cross the
+    /// boundaries of the MAX_TRANSACTION_BLOCKS blocks transactions
+    pub(crate) fn write_segments<'a>(
+        &mut self,
+        start: u32,
+        num_blocks: usize,
+        mut segments: impl Iterator<Item = &'a [u8]>,
+    ) -> Result<(), WriteBlocksError> {
+        let mut address = start;
+        for count in transaction_sizes(num_blocks) {
+            let mut transaction = self
+                .init_write_blocks(address, count)
+                .map_err(|error| WriteBlocksError { error, written: address - start })?;
+            let mut result = Ok(());
+            while result.is_ok() && transaction.remain > 0 {
+                result = match segments.next() {
+                    Some(segment) => self.start_write_blocks(&mut transaction, segment),
+                    None => Err(MciError::IncorrectDataSize),
+                };
+            }
+            let result =
+                result.and_then(|_| self.wait_end_of_write_blocks(false, &mut transaction));
+            if let Err(error) = result {
+                let written = self.recover_written_blocks(&mut transaction);
+                return Err(WriteBlocksError { error, written: address - start + written as u32 });
+            }
+            address += count as u32;
+        }
+        Ok(())
+    }
+
+    /// Command argument of a block address
+    /// SDSC Card (CCS=0) uses byte unit address,
+    /// SDHC and SDXC Cards (CCS=1) use block unit address (512 Bytes unit).
+    pub(crate) fn block_address(&self, block: u32) -> u32 {
+        if self.card.card_type.high_capacity() {
+            block
+        } else {
+            block * SD_MMC_BLOCK_SIZE as u32
+        }
+    }
+
+    /// CMD23 - Define the number of blocks of the following multiple block transfer
+    /// Returns whether the transfer is predefined, open-ended transfers are used if the card
+    /// does not support it
+    fn set_block_count(&mut self, num_blocks: u16, reliable_write: bool) -> Result<bool, MciError> {
+        if num_blocks <= 1 || !self.card.set_block_count {
+            return Ok(false);
+        }
+        let mut arg = num_blocks as u32;
+        arg.set_bit(31, reliable_write);
+        self.card.bus.send_command(MMC_CMD23_SET_BLOCK_COUNT.into(), arg)?;
+        Ok(true)
+    }
+
+    /// Bring the card back to transfer state after a failed write transaction
+    /// Returns the number of blocks written by the transaction, from ACMD22 for SD multiple
+    /// block writes, 0 if unknown
+    /// transaction.remain is updated
+    pub(crate) fn recover_written_blocks(&mut self, transaction: &mut Transaction) -> u16 {
+        if self.card.card_type.sd() && transaction.total > 1 {
+            if let Ok(written) = self.recover_write_blocks(transaction) {
+                return written;
+            }
+        } else {
+            self.abort_transaction(transaction);
+        }
+        transaction.remain = transaction.total;
+        0
+    }
+
+    /// Bring the card back to transfer state after a failed transaction
+    /// Errors are ignored, the original error of the transaction is reported instead
+    pub(crate) fn abort_transaction(&mut self, transaction: &Transaction) {
+        if transaction.total > 1 {
+            self.card.bus.adtc_stop(SDMMC_CMD12_STOP_TRANSMISSION.into(), 0).ok();
+        }
+        self.load_status().ok();
+    }
+}
+
+/// Number of blocks of each of the transactions transferring `num_blocks` blocks
+fn transaction_sizes(num_blocks: usize) -> impl Iterator<Item = u16> {
+    let starts = (0..num_blocks).step_by(MAX_TRANSACTION_BLOCKS);
+    starts.map(move |first| (num_blocks - first).min(MAX_TRANSACTION_BLOCKS) as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec;
+
+    use super::mock::{self, MockBus};
+    use super::*;
+
+    const BLOCK: usize = SD_MMC_BLOCK_SIZE;
+
+    #[test]
+    fn read_single_block() {
+        let mut controller = mock::controller(MockBus::default(), false);
+        let mut buf = [0u8; BLOCK];
+        assert!(controller.read_blocks(8, &mut buf).is_ok());
+        assert_eq!(*controller.card.bus.commands.borrow(), [(13, 1 << 16), (17, 8)]);
+        assert!(buf.iter().all(|&b| b == 0xA5));
+    }
+
+    #[test]
+    fn read_multiple_blocks() {
+        let mut controller = mock::controller(MockBus::default(), false);
+        let mut buf = [0u8; 4 * BLOCK];
+        assert!(controller.read_blocks(8, &mut buf).is_ok());
+        assert_eq!(controller.card.bus.indexes(), [13, 18, 12]);
+
+        // Predefined transfers are not stopped
+        let mut controller = mock::controller(MockBus::default(), false);
+        controller.card.set_block_count = true;
+        assert!(controller.read_blocks(8, &mut buf).is_ok());
+        assert_eq!(*controller.card.bus.commands.borrow(), [(13, 1 << 16), (23, 4), (18, 8)]);
+    }
+
+    #[test]
+    fn read_byte_addressed() {
+        let mut controller = mock::controller(MockBus::default(), false);
+        controller.card.card_type.set_high_capacity(false);
+        let mut buf = [0u8; BLOCK];
+        assert!(controller.read_blocks(3, &mut buf).is_ok());
+        assert_eq!(controller.card.bus.commands.borrow()[1], (17, 3 * BLOCK as u32));
+    }
+
+    #[test]
+    fn read_chunks() {
+        let mut controller = mock::controller(MockBus::default(), false);
+        let mut buf = vec![0u8; (MAX_TRANSACTION_BLOCKS + 1) * BLOCK];
+        assert!(controller.read_blocks(10, &mut buf).is_ok());
+        let last = 10 + MAX_TRANSACTION_BLOCKS as u32;
+        assert_eq!(
+            *controller.card.bus.commands.borrow(),
+            [(13, 1 << 16), (18, 10), (12, 0), (13, 1 << 16), (17, last)]
+        );
+    }
+
+    #[test]
+    fn read_error_aborts() {
+        let bus = MockBus { fail_transfer: Some(0), ..MockBus::default() };
+        let mut controller = mock::controller(bus, false);
+        let mut buf = [0u8; 2 * BLOCK];
+        assert!(matches!(controller.read_blocks(0, &mut buf), Err(MciError::ReadError)));
+        assert_eq!(controller.card.bus.indexes(), [13, 18, 12, 13]);
+    }
+
+    #[test]
+    fn read_size() {
+        let mut controller = mock::controller(MockBus::default(), false);
+        let mut buf = [0u8; BLOCK + 1];
+        assert!(matches!(controller.read_blocks(0, &mut buf), Err(MciError::IncorrectDataSize)));
+        assert!(matches!(controller.read_blocks(0, &mut []), Err(MciError::IncorrectDataSize)));
+        assert!(controller.card.bus.indexes().is_empty());
+    }
+
+    #[test]
+    fn write_blocks() {
+        let mut controller = mock::controller(MockBus::default(), false);
+        assert!(controller.write_blocks(8, &[1u8; BLOCK]).is_ok());
+        assert_eq!(*controller.card.bus.commands.borrow(), [(24, 8)]);
+
+        let mut controller = mock::controller(MockBus::default(), false);
+        assert!(controller.write_blocks(8, &[1u8; 3 * BLOCK]).is_ok());
+        assert_eq!(*controller.card.bus.commands.borrow(), [(25, 8), (12, 0)]);
+        assert_eq!(controller.card.bus.writes, [vec![1u8; 3 * BLOCK]]);
+
+        let mut controller = mock::controller(MockBus::default(), false);
+        controller.card.set_block_count = true;
+        assert!(controller.write_blocks(8, &[1u8; 3 * BLOCK]).is_ok());
+        assert_eq!(*controller.card.bus.commands.borrow(), [(23, 3), (25, 8)]);
+    }
+
+    #[test]
+    fn write_chunks() {
+        let mut controller = mock::controller(MockBus::default(), false);
+        controller.card.set_block_count = true;
+        let buf = vec![0u8; (MAX_TRANSACTION_BLOCKS + 2) * BLOCK];
+        assert!(controller.write_blocks(0, &buf).is_ok());
+        let last = MAX_TRANSACTION_BLOCKS as u32;
+        assert_eq!(
+            *controller.card.bus.commands.borrow(),
+            [(23, MAX_TRANSACTION_BLOCKS as u32), (25, 0), (23, 2), (25, last)]
+        );
+        assert_eq!(controller.card.bus.writes[0].len(), MAX_TRANSACTION_BLOCKS * BLOCK);
+        assert_eq!(controller.card.bus.writes[1].len(), 2 * BLOCK);
+    }
+
+    #[test]
+    fn write_error_reports_written_blocks() {
+        // Second transaction fails, ACMD22 reports 5 blocks written by it
+        let bus = MockBus { fail_transfer: Some(1), written_blocks: 5, ..MockBus::default() };
+        let mut controller = mock::controller(bus, false);
+        let buf = vec![0u8; (MAX_TRANSACTION_BLOCKS + 10) * BLOCK];
+        let error = controller.write_blocks(0, &buf).unwrap_err();
+        assert!(matches!(error.error, MciError::WriteError));
+        assert_eq!(error.written, MAX_TRANSACTION_BLOCKS as u32 + 5);
+        assert_eq!(controller.card.bus.indexes(), [25, 12, 25, 12, 13, 55, 22]);
+    }
+
+    #[test]
+    fn write_error_mmc() {
+        // No ACMD22 for MMC, the blocks of the failed transaction are reported as not written
+        let bus = MockBus { fail_transfer: Some(0), ..MockBus::default() };
+        let mut controller = mock::controller(bus, true);
+        let error = controller.write_blocks(0, &[0u8; 4 * BLOCK]).unwrap_err();
+        assert!(matches!(error.error, MciError::WriteError));
+        assert_eq!(error.written, 0);
+        assert_eq!(controller.card.bus.indexes(), [25, 12, 13]);
+    }
 }
@@ -0,0 +1,77 @@
+use bit_field::BitField;
+use embedded_error::mci::MciError;
+use embedded_error::ImplError;
+use embedded_hal::digital::v2::InputPin;
+
+use crate::bus::{Adtc, Bus, Read, Write};
+use crate::command_arguments::mmc::Partition;
+use crate::mode_index::ModeIndex;
+
+use super::controller::Controller;
+
+impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT> {
+    /// Partition size in KBytes, 0 if the partition does not exist
+    pub fn partition_size(&self, partition: Partition) -> u32 {
+        let ext_csd = &self.card.ext_csd;
+        match partition {
+            Partition::User => self.card.capacity,
+            Partition::Boot1 | Partition::Boot2 => ext_csd.boot_partition_size(),
+            Partition::Rpmb => ext_csd.rpmb_partition_size(),
+            Partition::GeneralPurpose(n) if n < 4 => ext_csd.gp_partition_size(n as usize),
+            Partition::GeneralPurpose(_) => 0,
+        }
+    }
+
+    /// Partition currently accessed, always the user area for SD cards
+    pub fn partition(&self) -> Partition {
+        if !self.card.card_type.mmc() {
+            return Partition::User;
+        }
+        self.card.ext_csd.partition_access().into()
+    }
+
+    /// CMD6 for MMC - Select the partition accessed by the following commands
+    /// self.card.ext_csd is updated
+    pub fn select_partition(&mut self, partition: Partition) -> Result<(), MciError> {
+        if self.partition_size(partition) == 0 {
+            return Err(MciError::Impl(ImplError::InvalidConfiguration));
+        }
+        if self.partition() == partition {
+            return Ok(());
+        }
+        let mut config = self.card.ext_csd.partition_config();
+        config.set_bits(0..3, partition.into());
+        let timeout_ms = self.card.ext_csd.partition_switch_time();
+        self.mmc_switch(ModeIndex::BootConfig, config, timeout_ms)
+    }
+
+    /// Run `f` with `partition` selected, then switch back to the user area
+    pub fn with_partition<T, F>(&mut self, partition: Partition, f: F) -> Result<T, MciError>
+    where
+        F: FnOnce(&mut Self) -> Result<T, MciError>,
+    {
+        self.select_partition(partition)?;
+        let result = f(self);
+        let restored = self.select_partition(Partition::User);
+        let value = result?;
+        restored?;
+        Ok(value)
+    }
+
+    /// Check that a block transfer stays within the accessed partition
+    pub(crate) fn check_partition_range(
+        &self,
+        start: u32,
+        num_blocks: u32,
+    ) -> Result<(), MciError> {
+        let partition = self.partition();
+        if partition == Partition::User {
+            return Ok(());
+        }
+        let size = self.partition_size(partition) as u64 * 2;
+        if start as u64 + num_blocks as u64 > size {
+            return Err(MciError::IncorrectDataSize);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use embedded_error::mci::MciError;
+use embedded_error::ImplError;
+use embedded_hal::digital::v2::InputPin;
+
+use crate::bus::{Adtc, Bus, Read, SdMmcBus, Write};
+use crate::card::State;
+use crate::command_arguments::mmc::PowerOffNotification;
+use crate::commands::{
+    MMC_CMD5_SLEEP_AWAKE, SDMMC_CMD7_DESELECT_CARD_CMD, SDMMC_CMD7_SELECT_CARD_CMD,
+};
+use crate::mode_index::ModeIndex;
+
+use super::controller::Controller;
+
+// CMD5 argument bit requesting the sleep state, awake if cleared
+const CMD5_SLEEP: u32 = 1 << 15;
+
+impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT> {
+    /// CMD6 for MMC - Write POWER_OFF_NOTIFICATION
+    /// PoweredOn is set at initialization, PowerOffShort or PowerOffLong shall be sent before
+    /// removing VCC. Waits up to the timeout given by EXT_CSD for the notification.
+    /// self.card.ext_csd is updated
+    pub fn mmc_power_off_notify(
+        &mut self,
+        notification: PowerOffNotification,
+    ) -> Result<(), MciError> {
+        if !self.card.card_type.mmc() || !self.card.ext_csd.power_off_notification_supported() {
+            return Err(MciError::Impl(ImplError::InvalidConfiguration));
+        }
+        if notification == PowerOffNotification::SleepNotification
+            && !self.card.ext_csd.sleep_notification_supported()
+        {
+            return Err(MciError::Impl(ImplError::InvalidConfiguration));
+        }
+        let timeout_ms = match notification {
+            PowerOffNotification::PowerOffLong => self.card.ext_csd.power_off_long_time(),
+            PowerOffNotification::SleepNotification => {
+                // Microseconds rounded up to milliseconds
+                self.card.ext_csd.sleep_notification_time().div_ceil(1000)
+            }
+            _ => 0,
+        };
+        self.mmc_switch(ModeIndex::PowerOffNotification, notification as u8, timeout_ms)
+    }
+}
+
+impl<BUS: SdMmcBus, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT> {
+    /// CMD7 then CMD5 for MMC - Put the card in sleep state
+    /// The card is notified first when power off notification is enabled, from eMMC 5.0.
+    /// The card shall be woken up with mmc_awake before any other access.
+    /// self.card.state is updated
+    pub fn mmc_sleep(&mut self) -> Result<(), MciError> {
+        if !self.card.card_type.mmc() {
+            return Err(MciError::Impl(ImplError::InvalidConfiguration));
+        }
+        self.select()?;
+        self.load_status()?;
+        // Cached data may be lost in sleep state
+        self.flush()?;
+        if self.card.ext_csd.sleep_notification_supported()
+            && self.card.ext_csd.power_off_notification() == PowerOffNotification::PoweredOn as u8
+        {
+            self.mmc_power_off_notify(PowerOffNotification::SleepNotification)?;
+        }
+        // Sleep is only accepted in stand-by state
+        self.card.bus.send_command(SDMMC_CMD7_DESELECT_CARD_CMD.into(), 0)?;
+        self.card
+            .bus
+            .send_command(MMC_CMD5_SLEEP_AWAKE.into(), (self.card.rca as u32) << 16 | CMD5_SLEEP)?;
+        self.card.state = State::Sleep;
+        self.mmc_wait_sleep_awake()?;
+        Ok(())
+    }
+
+    /// CMD5 then CMD7 for MMC - Wake the card up from sleep state and select it back
+    /// self.card.state is updated
+    pub fn mmc_awake(&mut self) -> Result<(), MciError> {
+        if self.card.state != State::Sleep {
+            return Err(MciError::Impl(ImplError::InvalidConfiguration));
+        }
+        self.select()?;
+        self.card.bus.send_command(MMC_CMD5_SLEEP_AWAKE.into(), (self.card.rca as u32) << 16)?;
+        self.mmc_wait_sleep_awake()?;
+        self.card
+            .bus
+            .send_command(SDMMC_CMD7_SELECT_CARD_CMD.into(), (self.card.rca as u32) << 16)?;
+        let status = self.load_status()?;
+        self.card.state = if status.card_is_locked() { State::Locked } else { State::Ready };
+        if self.card.ext_csd.power_off_notification()
+            == PowerOffNotification::SleepNotification as u8
+        {
+            self.mmc_power_off_notify(PowerOffNotification::PoweredOn)?;
+        }
+        Ok(())
+    }
+
+    /// Wait up to EXT_CSD S_A_TIMEOUT for the end of busy of CMD5
+    /// CMD13 cannot be used since the card does not answer it in sleep state
+    fn mmc_wait_sleep_awake(&mut self) -> Result<(), MciError> {
+        // One poll is 74 clock cycles
+        let timeout_ns = self.card.ext_csd.sleep_awake_timeout() as u64;
+        let retries = timeout_ns * self.card.clock as u64 / 1_000_000_000 / 74 + 2;
+        for _ in 0..retries {
+            if !self.card.bus.is_busy()? {
+                return Ok(());
+            }
+            self.card.bus.send_clock()?;
+        }
+        Err(MciError::Impl(ImplError::TimedOut))
+    }
+}
@@ -0,0 +1,419 @@
+use bit_field::BitField;
+use embedded_error::mci::MciError;
+use embedded_error::ImplError;
+use embedded_hal::digital::v2::InputPin;
+
+use crate::bus::{Adtc, Bus, Read, Write};
+use crate::command_arguments::mmc::Partition;
+use crate::command_arguments::rpmb::{
+    RpmbFrame, RpmbRequest, RpmbResult, RPMB_DATA_SIZE, RPMB_FRAME_SIZE, RPMB_KEY_SIZE,
+};
+use crate::commands::{
+    MMC_CMD23_SET_BLOCK_COUNT, SDMMC_CMD18_READ_MULTIPLE_BLOCK, SDMMC_CMD25_WRITE_MULTIPLE_BLOCK,
+};
+use crate::rpmb::RpmbKey;
+
+use super::controller::Controller;
+
+impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT> {
+    /// Program the authentication key, this can be done only once in the device lifetime
+    pub fn rpmb_program_key(&mut self, key: &[u8; RPMB_KEY_SIZE]) -> Result<(), MciError> {
+        let mut frame = RpmbFrame::new(RpmbRequest::ProgramKey);
+        frame.set_key_mac(key);
+        self.with_partition(Partition::Rpmb, |this| {
+            this.rpmb_send(&frame, true)?;
+            this.rpmb_result(RpmbRequest::ProgramKey).map(|_| ())
+        })
+    }
+
+    /// Read the authenticated write counter
+    pub fn rpmb_write_counter<K: RpmbKey>(&mut self, key: &mut K) -> Result<u32, MciError> {
+        self.with_partition(Partition::Rpmb, |this| this.rpmb_read_counter(key))
+    }
+
+    /// Authenticated read of `destination.len()` bytes at `address` in half sectors
+    /// The destination size must be a multiple of RPMB_DATA_SIZE
+    pub fn rpmb_read<K: RpmbKey>(
+        &mut self,
+        key: &mut K,
+        address: u16,
+        destination: &mut [u8],
+    ) -> Result<(), MciError> {
+        if destination.is_empty() || !destination.len().is_multiple_of(RPMB_DATA_SIZE) {
+            return Err(MciError::IncorrectDataSize);
+        }
+        self.rpmb_check_range(address, destination.len())?;
+        self.with_partition(Partition::Rpmb, |this| {
+            for (i, data) in destination.chunks_mut(RPMB_DATA_SIZE).enumerate() {
+                let nonce = key.nonce();
+                let mut request = RpmbFrame::new(RpmbRequest::AuthenticatedRead);
+                request.set_address(address + i as u16).set_nonce(&nonce);
+                this.rpmb_send(&request, false)?;
+                let response = this.rpmb_receive()?;
+                this.rpmb_verify(key, &response, RpmbRequest::AuthenticatedRead)?;
+                if response.nonce() != nonce {
+                    return Err(MciError::Impl(ImplError::PermissionDenied));
+                }
+                data.copy_from_slice(response.data());
+            }
+            Ok(())
+        })
+    }
+
+    /// Authenticated write of `source` at `address` in half sectors
+    /// The source size must be a multiple of RPMB_DATA_SIZE
+    pub fn rpmb_write<K: RpmbKey>(
+        &mut self,
+        key: &mut K,
+        address: u16,
+        source: &[u8],
+    ) -> Result<(), MciError> {
+        if source.is_empty() || !source.len().is_multiple_of(RPMB_DATA_SIZE) {
+            return Err(MciError::IncorrectDataSize);
+        }
+        self.rpmb_check_range(address, source.len())?;
+        self.with_partition(Partition::Rpmb, |this| {
+            let mut counter = this.rpmb_read_counter(key)?;
+            for (i, data) in source.chunks(RPMB_DATA_SIZE).enumerate() {
+                let mut request = RpmbFrame::new(RpmbRequest::AuthenticatedWrite);
+                request
+                    .set_data(data)
+                    .set_address(address + i as u16)
+                    .set_block_count(1)
+                    .set_write_counter(counter);
+                let mac = key.mac(&[request.authenticated()])?;
+                request.set_key_mac(&mac);
+                this.rpmb_send(&request, true)?;
+                let response = this.rpmb_result(RpmbRequest::AuthenticatedWrite)?;
+                this.rpmb_verify(key, &response, RpmbRequest::AuthenticatedWrite)?;
+                if response.write_counter() != counter.wrapping_add(1) {
+                    return Err(MciError::Impl(ImplError::PermissionDenied));
+                }
+                counter = response.write_counter();
+            }
+            Ok(())
+        })
+    }
+
+    /// Check that `len` bytes from `address` in half sectors fit in the RPMB partition
+    fn rpmb_check_range(&self, address: u16, len: usize) -> Result<(), MciError> {
+        // Partition size in KBytes, frame addresses are 16 bits
+        let half_sectors = self.partition_size(Partition::Rpmb) as usize * 1024 / RPMB_DATA_SIZE;
+        let half_sectors = half_sectors.min(u16::MAX as usize + 1);
+        if address as usize + len / RPMB_DATA_SIZE > half_sectors {
+            return Err(MciError::IncorrectDataSize);
+        }
+        Ok(())
+    }
+
+    fn rpmb_read_counter<K: RpmbKey>(&mut self, key: &mut K) -> Result<u32, MciError> {
+        let nonce = key.nonce();
+        let mut request = RpmbFrame::new(RpmbRequest::ReadWriteCounter);
+        request.set_nonce(&nonce);
+        self.rpmb_send(&request, false)?;
+        let response = self.rpmb_receive()?;
+        self.rpmb_verify(key, &response, RpmbRequest::ReadWriteCounter)?;
+        if response.nonce() != nonce {
+            return Err(MciError::Impl(ImplError::PermissionDenied));
+        }
+        Ok(response.write_counter())
+    }
+
+    /// Request and read the result of a key programming or authenticated write
+    fn rpmb_result(&mut self, request: RpmbRequest) -> Result<RpmbFrame, MciError> {
+        self.rpmb_send(&RpmbFrame::new(RpmbRequest::ReadResult), false)?;
+        let response = self.rpmb_receive()?;
+        if !response.is_response(request) {
+            return Err(MciError::Impl(ImplError::Internal));
+        }
+        rpmb_error(response.result())?;
+        Ok(response)
+    }
+
+    /// Check the response type, the result and the MAC of a response
+    fn rpmb_verify<K: RpmbKey>(
+        &mut self,
+        key: &mut K,
+        response: &RpmbFrame,
+        request: RpmbRequest,
+    ) -> Result<(), MciError> {
+        if !response.is_response(request) {
+            return Err(MciError::Impl(ImplError::Internal));
+        }
+        rpmb_error(response.result())?;
+        if !constant_time_eq(&key.mac(&[response.authenticated()])?, response.key_mac()) {
+            return Err(MciError::Impl(ImplError::PermissionDenied));
+        }
+        Ok(())
+    }
+
+    /// CMD23 + CMD25 - Send a frame to the RPMB partition
+    fn rpmb_send(&mut self, frame: &RpmbFrame, reliable_write: bool) -> Result<(), MciError> {
+        let mut arg = 1u32;
+        arg.set_bit(31, reliable_write);
+        self.card.bus.send_command(MMC_CMD23_SET_BLOCK_COUNT.into(), arg)?;
+        let cmd = SDMMC_CMD25_WRITE_MULTIPLE_BLOCK.into();
+        self.card.bus.adtc_start(cmd, 0, RPMB_FRAME_SIZE as u16, 1, true)?;
+        self.card.bus.write_blocks(frame.as_bytes())?;
+        self.card.bus.wait_until_write_finished()?;
+        self.load_status()?;
+        Ok(())
+    }
+
+    /// CMD23 + CMD18 - Read a response frame from the RPMB partition
+    fn rpmb_receive(&mut self) -> Result<RpmbFrame, MciError> {
+        let mut frame = RpmbFrame::default();
+        self.card.bus.send_command(MMC_CMD23_SET_BLOCK_COUNT.into(), 1)?;
+        let cmd = SDMMC_CMD18_READ_MULTIPLE_BLOCK.into();
+        self.card.bus.adtc_start(cmd, 0, RPMB_FRAME_SIZE as u16, 1, true)?;
+        self.card.bus.read_blocks(&mut frame.0)?;
+        self.card.bus.wait_until_read_finished()?;
+        Ok(frame)
+    }
+}
+
+/// Compare without early exit so the timing does not leak the matching prefix
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn rpmb_error(result: RpmbResult) -> Result<(), MciError> {
+    match result {
+        RpmbResult::Ok => Ok(()),
+        RpmbResult::AuthenticationFailure | RpmbResult::CounterFailure => {
+            Err(MciError::Impl(ImplError::PermissionDenied))
+        }
+        RpmbResult::AddressFailure => Err(MciError::IncorrectDataSize),
+        RpmbResult::WriteFailure => Err(MciError::WriteError),
+        RpmbResult::ReadFailure => Err(MciError::ReadError),
+        RpmbResult::KeyNotProgrammed => Err(MciError::Impl(ImplError::InvalidConfiguration)),
+        RpmbResult::GeneralFailure => Err(MciError::Impl(ImplError::Internal)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use crate::command_arguments::rpmb::RPMB_NONCE_SIZE;
+    use crate::controller::mock::{self, MockBus};
+    use crate::dummy_input_pin::DummyInputPin;
+    use crate::registers::ext_csd::EXT_CSD_RPMB_SIZE_MULT_INDEX;
+
+    use super::*;
+
+    /// Key with a checksum as MAC and nonces counting from 1
+    #[derive(Default)]
+    struct TestKey {
+        nonce: u8,
+    }
+
+    impl RpmbKey for TestKey {
+        fn mac(&mut self, frames: &[&[u8]]) -> Result<[u8; RPMB_KEY_SIZE], MciError> {
+            let mut mac = [0u8; RPMB_KEY_SIZE];
+            for (i, byte) in frames.iter().flat_map(|frame| frame.iter()).enumerate() {
+                mac[i % RPMB_KEY_SIZE] ^= byte.wrapping_add(i as u8);
+            }
+            Ok(mac)
+        }
+
+        fn nonce(&mut self) -> [u8; RPMB_NONCE_SIZE] {
+            self.nonce += 1;
+            [self.nonce; RPMB_NONCE_SIZE]
+        }
+    }
+
+    fn response(request: RpmbRequest, result: RpmbResult) -> RpmbFrame {
+        let mut frame = RpmbFrame::default();
+        frame.0[508..510].copy_from_slice(&(result as u16).to_be_bytes());
+        frame.0[510..].copy_from_slice(&((request as u16) << 8).to_be_bytes());
+        frame
+    }
+
+    fn signed(mut frame: RpmbFrame) -> Vec<u8> {
+        let mac = TestKey::default().mac(&[frame.authenticated()]).unwrap_or_default();
+        frame.set_key_mac(&mac);
+        frame.0.to_vec()
+    }
+
+    fn counter_response(counter: u32, nonce: u8) -> Vec<u8> {
+        let mut frame = response(RpmbRequest::ReadWriteCounter, RpmbResult::Ok);
+        frame.set_write_counter(counter).set_nonce(&[nonce; RPMB_NONCE_SIZE]);
+        signed(frame)
+    }
+
+    fn rpmb_controller(reads: Vec<Vec<u8>>) -> Controller<MockBus, DummyInputPin, DummyInputPin> {
+        let bus = MockBus { reads: reads.into(), ..Default::default() };
+        let mut controller = mock::controller(bus, true);
+        controller.card.ext_csd.0[EXT_CSD_RPMB_SIZE_MULT_INDEX] = 1;
+        controller
+    }
+
+    fn frame(bytes: &[u8]) -> RpmbFrame {
+        let mut frame = RpmbFrame::default();
+        frame.0.copy_from_slice(bytes);
+        frame
+    }
+
+    fn request_type(frame: &[u8]) -> u16 {
+        u16::from_be_bytes([frame[510], frame[511]])
+    }
+
+    #[test]
+    fn program_key() {
+        let key = [0x5A; RPMB_KEY_SIZE];
+        let ok = response(RpmbRequest::ProgramKey, RpmbResult::Ok);
+        let mut controller = rpmb_controller([ok.0.to_vec()].into());
+        assert!(controller.rpmb_program_key(&key).is_ok());
+        let bus = &controller.card.bus;
+        assert_eq!(bus.indexes(), [6, 13, 23, 25, 13, 23, 25, 13, 23, 18, 6, 13]);
+        assert_eq!(bus.commands.borrow()[2], (23, (1 << 31) | 1));
+        assert_eq!(request_type(&bus.writes[0]), RpmbRequest::ProgramKey as u16);
+        assert_eq!(&bus.writes[0][196..228], &key);
+        assert_eq!(request_type(&bus.writes[1]), RpmbRequest::ReadResult as u16);
+        assert_eq!(controller.partition(), Partition::User);
+
+        let failed = response(RpmbRequest::ProgramKey, RpmbResult::WriteFailure);
+        let mut controller = rpmb_controller([failed.0.to_vec()].into());
+        assert!(matches!(controller.rpmb_program_key(&key), Err(MciError::WriteError)));
+        assert_eq!(controller.partition(), Partition::User);
+
+        let other = response(RpmbRequest::AuthenticatedWrite, RpmbResult::Ok);
+        let mut controller = rpmb_controller([other.0.to_vec()].into());
+        assert!(matches!(
+            controller.rpmb_program_key(&key),
+            Err(MciError::Impl(ImplError::Internal))
+        ));
+    }
+
+    #[test]
+    fn write_counter() {
+        let mut controller = rpmb_controller([counter_response(7, 1)].into());
+        let mut key = TestKey::default();
+        assert!(matches!(controller.rpmb_write_counter(&mut key), Ok(7)));
+        let request = &controller.card.bus.writes[0];
+        assert_eq!(request_type(request), RpmbRequest::ReadWriteCounter as u16);
+        assert_eq!(&request[484..500], &[1; RPMB_NONCE_SIZE]);
+
+        // Replayed response with the nonce of a previous request
+        let mut controller = rpmb_controller([counter_response(7, 1)].into());
+        let mut key = TestKey { nonce: 1 };
+        assert!(matches!(
+            controller.rpmb_write_counter(&mut key),
+            Err(MciError::Impl(ImplError::PermissionDenied))
+        ));
+
+        let mut tampered = counter_response(7, 1);
+        tampered[500..504].copy_from_slice(&8u32.to_be_bytes());
+        let mut controller = rpmb_controller([tampered].into());
+        assert!(matches!(
+            controller.rpmb_write_counter(&mut TestKey::default()),
+            Err(MciError::Impl(ImplError::PermissionDenied))
+        ));
+
+        let not_programmed = response(RpmbRequest::ReadWriteCounter, RpmbResult::KeyNotProgrammed);
+        let mut controller = rpmb_controller([signed(not_programmed)].into());
+        assert!(matches!(
+            controller.rpmb_write_counter(&mut TestKey::default()),
+            Err(MciError::Impl(ImplError::InvalidConfiguration))
+        ));
+    }
+
+    #[test]
+    fn authenticated_write() {
+        let data = [0x3C; RPMB_DATA_SIZE];
+        let mut written = response(RpmbRequest::AuthenticatedWrite, RpmbResult::Ok);
+        written.set_write_counter(8).set_address(2);
+        let reads = [counter_response(7, 1), signed(written)];
+        let mut controller = rpmb_controller(reads.into());
+        let mut key = TestKey::default();
+        assert!(controller.rpmb_write(&mut key, 2, &data).is_ok());
+        let bus = &controller.card.bus;
+        assert_eq!(
+            bus.indexes(),
+            [6, 13, 23, 25, 13, 23, 18, 23, 25, 13, 23, 25, 13, 23, 18, 6, 13]
+        );
+        let request = frame(&bus.writes[1]);
+        assert_eq!(request_type(&request.0), RpmbRequest::AuthenticatedWrite as u16);
+        assert_eq!(request.data(), &data);
+        assert_eq!(request.address(), 2);
+        assert_eq!(request.write_counter(), 7);
+        let mac = TestKey::default().mac(&[request.authenticated()]).unwrap_or_default();
+        assert_eq!(request.key_mac(), &mac);
+        assert_eq!(request_type(&bus.writes[2]), RpmbRequest::ReadResult as u16);
+
+        // The counter must be incremented by the write
+        let mut stale = response(RpmbRequest::AuthenticatedWrite, RpmbResult::Ok);
+        stale.set_write_counter(7);
+        let mut controller = rpmb_controller([counter_response(7, 1), signed(stale)].into());
+        assert!(matches!(
+            controller.rpmb_write(&mut TestKey::default(), 2, &data),
+            Err(MciError::Impl(ImplError::PermissionDenied))
+        ));
+
+        let mut unsigned = response(RpmbRequest::AuthenticatedWrite, RpmbResult::Ok);
+        unsigned.set_write_counter(8);
+        let mut controller = rpmb_controller([counter_response(7, 1), unsigned.0.to_vec()].into());
+        assert!(matches!(
+            controller.rpmb_write(&mut TestKey::default(), 2, &data),
+            Err(MciError::Impl(ImplError::PermissionDenied))
+        ));
+
+        let failed = response(RpmbRequest::AuthenticatedWrite, RpmbResult::CounterFailure);
+        let mut controller = rpmb_controller([counter_response(7, 1), signed(failed)].into());
+        assert!(matches!(
+            controller.rpmb_write(&mut TestKey::default(), 2, &data),
+            Err(MciError::Impl(ImplError::PermissionDenied))
+        ));
+    }
+
+    #[test]
+    fn authenticated_read() {
+        let mut reads = Vec::new();
+        for nonce in 1..=2 {
+            let mut frame = response(RpmbRequest::AuthenticatedRead, RpmbResult::Ok);
+            frame.set_data(&[nonce; RPMB_DATA_SIZE]).set_nonce(&[nonce; RPMB_NONCE_SIZE]);
+            reads.push(signed(frame));
+        }
+        let mut controller = rpmb_controller(reads);
+        let mut destination = [0u8; 2 * RPMB_DATA_SIZE];
+        assert!(controller.rpmb_read(&mut TestKey::default(), 4, &mut destination).is_ok());
+        assert_eq!(&destination[..RPMB_DATA_SIZE], &[1; RPMB_DATA_SIZE]);
+        assert_eq!(&destination[RPMB_DATA_SIZE..], &[2; RPMB_DATA_SIZE]);
+        let bus = &controller.card.bus;
+        assert_eq!(bus.indexes(), [6, 13, 23, 25, 13, 23, 18, 23, 25, 13, 23, 18, 6, 13]);
+        assert_eq!(bus.commands.borrow()[2], (23, 1));
+        let request = frame(&bus.writes[1]);
+        assert_eq!(request_type(&request.0), RpmbRequest::AuthenticatedRead as u16);
+        assert_eq!(request.address(), 5);
+        assert_eq!(request.nonce(), &[2; RPMB_NONCE_SIZE]);
+
+        let mut frame = response(RpmbRequest::AuthenticatedWrite, RpmbResult::Ok);
+        frame.set_nonce(&[1; RPMB_NONCE_SIZE]);
+        let mut controller = rpmb_controller([signed(frame)].into());
+        let mut destination = [0u8; RPMB_DATA_SIZE];
+        assert!(matches!(
+            controller.rpmb_read(&mut TestKey::default(), 0, &mut destination),
+            Err(MciError::Impl(ImplError::Internal))
+        ));
+
+        let mut frame = response(RpmbRequest::AuthenticatedRead, RpmbResult::Ok);
+        frame.set_data(&[1; RPMB_DATA_SIZE]).set_nonce(&[1; RPMB_NONCE_SIZE]);
+        let mut tampered = signed(frame);
+        tampered[300] ^= 1;
+        let mut controller = rpmb_controller([tampered].into());
+        assert!(matches!(
+            controller.rpmb_read(&mut TestKey::default(), 0, &mut destination),
+            Err(MciError::Impl(ImplError::PermissionDenied))
+        ));
+        assert_eq!(destination, [0u8; RPMB_DATA_SIZE]);
+
+        let mut controller = rpmb_controller(Vec::new());
+        assert!(matches!(
+            controller.rpmb_read(&mut TestKey::default(), 512, &mut destination),
+            Err(MciError::IncorrectDataSize)
+        ));
+    }
+}
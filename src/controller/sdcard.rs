@@ -3,22 +3,25 @@ use embedded_error::mci::MciError;
 use embedded_error::ImplError;
 use embedded_hal::digital::v2::InputPin;
 
-use crate::bus::{Adtc, Bus, Read, Write};
+use crate::bus::{Adtc, Bus, Read, Timing, Write};
 use crate::card::version::{CardVersion, SdCardVersion};
 use crate::card::{SD_MMC_TRANS_UNITS, SD_TRANS_MULTIPLIERS};
-use crate::command_arguments::sd::cmd6::{Cmd6, Cmd6Mode};
+use crate::command_arguments::sd::cmd6::{Cmd6, Cmd6Mode, CurrentLimit, DriverStrength};
 use crate::command_arguments::sd::cmd8::Cmd8;
 use crate::command_flags::CommandFlag;
 use crate::command_responses::Response;
 use crate::commands::{
-    Command, MMC_MCI_CMD1_SEND_OP_COND, SDMMC_CMD55_APP_CMD, SD_ACMD51_SEND_SCR,
-    SD_CMD6_SWITCH_FUNC, SD_CMD8_SEND_IF_COND, SD_MCI_ACMD41_SD_SEND_OP_COND,
+    Command, MMC_MCI_CMD1_SEND_OP_COND, SDIO_CMD5_SEND_OP_COND, SDMMC_CMD55_APP_CMD,
+    SD_ACMD13_SD_STATUS, SD_ACMD22_SEND_NUM_WR_BLOCKS, SD_ACMD51_SEND_SCR, SD_CMD6_SWITCH_FUNC,
+    SD_CMD8_SEND_IF_COND, SD_MCI_ACMD41_SD_SEND_OP_COND,
 };
 use crate::registers::csd::SdCsdStructureVersion;
 use crate::registers::ocr::{AccessMode, OcrRegister};
 use crate::registers::sd::scr::ScrRegister;
+use crate::registers::sd::sd_status::SdStatusRegister;
 use crate::registers::sd::switch_status::{SwitchStatusRegister, SD_SW_STATUS_FUN_GRP_RC_ERROR};
 use crate::sd::sd_physical_specification::SdPhysicalSpecification;
+use crate::transaction::Transaction;
 
 use super::controller::{ocr_voltage_support, Controller};
 
@@ -27,6 +30,13 @@ impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<
     /// # Arguments
     /// * `v2` Shall be true if it is a SD card V2
     pub fn load_ocr_sdcard(&mut self, v2: bool) -> Result<(), MciError> {
+        self.load_ocr_sdcard_1v8(v2, false).map(|_| ())
+    }
+
+    /// Ask all cards to send their operations conditions, with S18R if `s18r`
+    /// Returns whether the card accepts switching to 1.8V signaling
+    pub fn load_ocr_sdcard_1v8(&mut self, v2: bool, s18r: bool) -> Result<bool, MciError> {
+        let mut s18a = false;
         // Timeout 1s = 400KHz / ((6+6+6+6)*8) cycles = 2100 retry
         for i in (0..2100).rev() {
             if i == 0 {
@@ -38,6 +48,7 @@ impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<
             self.card.bus.send_command(SDMMC_CMD55_APP_CMD.into(), 0)?;
             let mut arg = ocr_voltage_support();
             arg.val.set_bit(30, v2); // SD_ACMD41_HCS ACMD41 High Capacity Support
+            arg.set_switching_to_1_8v_accepted(s18r);
             self.card.bus.send_command(SD_MCI_ACMD41_SD_SEND_OP_COND.into(), arg.val)?;
             let resp = self.card.bus.get_response()?;
             let resp = OcrRegister { val: resp };
@@ -45,6 +56,40 @@ impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<
                 if resp.card_capacity_status() {
                     self.card.card_type.set_high_capacity(true);
                 }
+                s18a = s18r && resp.switching_to_1_8v_accepted();
+                break;
+            }
+        }
+        Ok(s18a)
+    }
+
+    /// Ask the SDIO card to send its operation condition (CMD5)
+    /// self.card.card_type is updated if an SDIO card is present
+    pub fn load_ocr_sdio(&mut self) -> Result<(), MciError> {
+        if self.card.bus.send_command(SDIO_CMD5_SEND_OP_COND.into(), 0).is_err() {
+            // No error but card type not updated
+            return Ok(());
+        }
+        let resp = OcrRegister { val: self.card.bus.get_response()? };
+        if !resp.number_of_io_functions() {
+            // No error but card type not updated
+            return Ok(());
+        }
+
+        let arg = resp.val & ocr_voltage_support().val;
+        // Wait until card is ready
+        // Timeout 1s = 400KHz / ((6+4)*8) cycles = 5000 retry
+        for i in (0..5000).rev() {
+            if i == 0 {
+                return Err(MciError::Impl(ImplError::TimedOut));
+            }
+            self.card.bus.send_command(SDIO_CMD5_SEND_OP_COND.into(), arg)?;
+            let resp = OcrRegister { val: self.card.bus.get_response()? };
+            if resp.card_powered_up_status() {
+                self.card.card_type.set_sdio(true);
+                if resp.memory_present() {
+                    self.card.card_type.set_sd(true);
+                }
                 break;
             }
         }
@@ -104,7 +149,7 @@ impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<
             .set_mode(Cmd6Mode::Switch);
         let status = self.cmd6(SD_CMD6_SWITCH_FUNC, arg)?;
 
-        if status.group1_info_status() == SD_SW_STATUS_FUN_GRP_RC_ERROR {
+        if status.group1_rc() as u16 == SD_SW_STATUS_FUN_GRP_RC_ERROR {
             // Not supported, not a protocol error
             return Ok(false);
         }
@@ -117,9 +162,46 @@ impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<
         self.card.bus.send_clock()?;
 
         self.card.high_speed = true;
+        self.card.timing = Timing::HighSpeed;
         self.card.clock *= 2;
 
-        Ok(false)
+        Ok(true)
+    }
+
+    /// CMD6 for SD - Query the functions supported by the card in each group
+    pub fn sd_function_support(&mut self) -> Result<SwitchStatusRegister, MciError> {
+        self.cmd6(SD_CMD6_SWITCH_FUNC, Cmd6::no_influence(Cmd6Mode::Check))
+    }
+
+    /// CMD6 for SD - Select the driver strength of the card
+    /// Returns false if the driver strength is not supported by the card
+    pub fn sd_set_driver_strength(&mut self, strength: DriverStrength) -> Result<bool, MciError> {
+        if self.sd_function_support()?.group3_info_status() & (1 << strength as u16) == 0 {
+            return Ok(false);
+        }
+        let mut arg = Cmd6::no_influence(Cmd6Mode::Switch);
+        arg.set_driver_strength(strength);
+        let status = self.cmd6(SD_CMD6_SWITCH_FUNC, arg)?;
+        if status.group3_busy() > 0 {
+            return Err(MciError::GroupBusy);
+        }
+        Ok(status.group3_rc() == strength as u8)
+    }
+
+    /// CMD6 for SD - Select the current limit of the card, only relevant to SDR50, SDR104 and
+    /// DDR50 bus speed modes
+    /// Returns false if the current limit is not supported by the card
+    pub fn sd_set_current_limit(&mut self, limit: CurrentLimit) -> Result<bool, MciError> {
+        if self.sd_function_support()?.group4_info_status() & (1 << limit as u16) == 0 {
+            return Ok(false);
+        }
+        let mut arg = Cmd6::no_influence(Cmd6Mode::Switch);
+        arg.set_current_limit(limit);
+        let status = self.cmd6(SD_CMD6_SWITCH_FUNC, arg)?;
+        if status.group4_busy() > 0 {
+            return Err(MciError::GroupBusy);
+        }
+        Ok(status.group4_rc() == limit as u8)
     }
 
     /// CMD8 for SD card - send interface condition command
@@ -176,12 +258,44 @@ impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<
         Ok(buf.into())
     }
 
+    /// ACMD13 - Read the SD Status register
+    pub fn sd_status(&mut self) -> Result<SdStatusRegister, MciError> {
+        let mut buf = [0u8; 64];
+        self.card.bus.send_command(SDMMC_CMD55_APP_CMD.into(), (self.card.rca as u32) << 16)?;
+        self.card.bus.adtc_start(SD_ACMD13_SD_STATUS.into(), 0, 64, 1, true)?;
+        self.card.bus.read_blocks(&mut buf)?;
+        self.card.bus.wait_until_read_finished()?;
+
+        Ok(buf.into())
+    }
+
+    /// ACMD22 - Recover from a failed multiple block write
+    /// Stops the transfer and gets the number of well written blocks from the card, the write
+    /// can be resumed at block `start + transaction.total - transaction.remain`.
+    /// transaction.remain is updated
+    pub fn recover_write_blocks(&mut self, transaction: &mut Transaction) -> Result<u16, MciError> {
+        if !self.card.card_type.sd() {
+            return Err(MciError::Impl(ImplError::InvalidConfiguration));
+        }
+        self.abort_transaction(transaction);
+        let mut buf = [0u8; 4];
+        self.card.bus.send_command(SDMMC_CMD55_APP_CMD.into(), (self.card.rca as u32) << 16)?;
+        self.card.bus.adtc_start(SD_ACMD22_SEND_NUM_WR_BLOCKS.into(), 0, 4, 1, true)?;
+        self.card.bus.read_blocks(&mut buf)?;
+        self.card.bus.wait_until_read_finished()?;
+
+        let written = u32::from_be_bytes(buf).min(transaction.total as u32) as u16;
+        transaction.remain = transaction.total - written;
+        Ok(written)
+    }
+
     /// ACMD51 - Read the SD Card configuration register (SCR)
     /// SCR provides information on the SD Memory Card's special features that were configured
     /// into the given card. The SCR register is 64 bits.
-    /// Updates self.version
+    /// Updates self.version, self.set_block_count
     pub fn sd_acmd51(&mut self) -> Result<(), MciError> {
         let scr = self.sd_scr()?;
+        self.card.set_block_count = scr.cmd23_supported();
         self.card.version = match scr.sd_specification_version() {
             SdPhysicalSpecification::Revision1d01 => CardVersion::SdCard(SdCardVersion::Sd1d0),
             SdPhysicalSpecification::Revision1d10 => CardVersion::SdCard(SdCardVersion::Sd1d10),
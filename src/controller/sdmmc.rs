@@ -4,12 +4,15 @@ use embedded_error::ImplError;
 use embedded_hal::digital::v2::InputPin;
 
 use crate::bus::{SdMmcBus, SD_MMC_BLOCK_SIZE};
-use crate::card::version::MmcVersion;
-use crate::command_arguments::mmc::BusWidth;
+use crate::card::version::{MmcVersion, SdCardVersion};
+use crate::card::State;
+use crate::command_arguments::mmc::{BusWidth, PowerOffNotification};
 use crate::commands::{
-    MMC_CMD3_SET_RELATIVE_ADDR, SDMMC_CMD16_SET_BLOCKLEN, SDMMC_CMD2_ALL_SEND_CID,
-    SDMMC_CMD7_SELECT_CARD_CMD, SDMMC_MCI_CMD0_GO_IDLE_STATE,
+    MMC_CMD3_SET_RELATIVE_ADDR, SDMMC_CMD16_SET_BLOCKLEN, SDMMC_CMD7_DESELECT_CARD_CMD,
+    SDMMC_CMD7_SELECT_CARD_CMD, SDMMC_MCI_CMD0_GO_IDLE_STATE, SD_CMD3_SEND_RELATIVE_ADDR,
 };
+use crate::registers::cid::CidRegister;
+use crate::registers::sd::card_status::CardStatusRegister;
 
 use super::controller::Controller;
 
@@ -22,10 +25,15 @@ impl<BUS: SdMmcBus, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT>
         // CMD0 - Reset all cards to idle state.
         self.card.bus.send_command(SDMMC_MCI_CMD0_GO_IDLE_STATE.into(), 0)?;
         self.load_ocr_mmc()?;
+        self.card.card_type.set_mmc(true);
+        self.mmc_identify()
+    }
 
-        // Put the card in Identify Mode
-        // Note: The CID is not used
-        self.card.bus.send_command(SDMMC_CMD2_ALL_SEND_CID.into(), 0)?;
+    /// Identification process for MMC once the OCR is loaded, then sets the card in transfer
+    /// state with maximum bus width and transfer speed.
+    fn mmc_identify(&mut self) -> Result<(), MciError> {
+        // Put the card in Identify Mode and get the card identification
+        self.card.mci_all_send_cid()?;
 
         //Assign relative address to the card
         self.card.rca = 1;
@@ -41,19 +49,18 @@ impl<BUS: SdMmcBus, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT>
         self.card
             .bus
             .send_command(SDMMC_CMD7_SELECT_CARD_CMD.into(), (self.card.rca as u32) << 16)?;
+        let locked = CardStatusRegister { val: self.card.bus.get_response()? }.card_is_locked();
 
         let version: usize = self.card.version.into();
+        self.card.set_block_count = version >= MmcVersion::Mmc4d0 as usize;
         if version >= MmcVersion::Mmc4d0 as usize {
             // For MMC 4.0 Higher version
             // Get EXT_CSD
             let authorize_high_speed = self.card.load_extcsd()?;
-            if BusWidth::_4BIT <= self.card.bus.get_bus_width(self.slot)? {
+            if !locked && BusWidth::_4BIT <= self.card.bus.get_bus_width(self.slot)? {
                 // Enable more bus width
-                let bus_width = self.card.bus_width;
-                self.card
-                    .set_bus_width(&bus_width)
+                self.mmc_select_bus_width()
                     .map_err(|_| MciError::Setup(SetupError::CouldNotSetBusWidth))?;
-                self.select().map_err(|_| MciError::Setup(SetupError::CouldNotSetToHighSpeed))?;
             }
             if self
                 .card
@@ -67,9 +74,167 @@ impl<BUS: SdMmcBus, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT>
                     .map_err(|_| MciError::Setup(SetupError::CouldNotSetToHighSpeed))?;
                 self.select()?;
             }
+            if !locked {
+                self.mmc_select_fastest_timing()
+                    .and_then(|_| self.mmc_set_ddr52())
+                    .map_err(|_| MciError::Setup(SetupError::CouldNotSetToHighSpeed))?;
+            }
+            if !locked && self.cache && self.card.ext_csd.cache_size() > 0 {
+                self.mmc_set_cache(true)?;
+            }
+            if !locked && self.card.ext_csd.power_off_notification_supported() {
+                // Required to notify the card before removing the power
+                self.mmc_power_off_notify(PowerOffNotification::PoweredOn)?;
+            }
         } else {
             self.select()?;
         }
+        self.set_block_length()?;
+        self.card.state = if locked { State::Locked } else { State::Ready };
+        Ok(())
+    }
+
+    /// Initialize the SD memory card in MCI mode
+    /// Runs CMD0, CMD8, ACMD41, CMD2, CMD3, CMD9 and CMD7 to put the card in transfer state,
+    /// then reads the SCR and switches to 4-bit bus width and high speed when possible.
+    /// A locked card is left in State::Locked with default bus settings, it shall be unlocked then
+    /// initialized again.
+    /// self.card.rca, self.card.version, self.card.capacity, self.card.bus_width and
+    /// self.card.clock are updated
+    pub fn init_sd(&mut self) -> Result<(), MciError> {
+        // CMD0 - Reset all cards to idle state.
+        self.card.bus.send_command(SDMMC_MCI_CMD0_GO_IDLE_STATE.into(), 0)?;
+        let v2 = self.is_v2()?;
+        // Try to get the SD card's operating condition
+        self.load_ocr_sd_uhs(v2)?;
+        self.card.card_type.set_sd(true);
+        self.sd_identify()
+    }
+
+    /// Identification process for SD memory card once the OCR is loaded, then sets the card in
+    /// transfer state with maximum bus width and transfer speed.
+    fn sd_identify(&mut self) -> Result<(), MciError> {
+        // Updated from the SCR
+        self.card.set_block_count = false;
+
+        // Put the card in Identify Mode and get the card identification
+        self.card.mci_all_send_cid()?;
+
+        // Ask the card to publish a new relative address (RCA)
+        self.card.bus.send_command(SD_CMD3_SEND_RELATIVE_ADDR.into(), 0)?;
+        self.card.rca = (self.card.bus.get_response()? >> 16) as u16;
+
+        // Get the card specific data
+        self.card.mci_load_csd()?;
+        self.sd_decode_csd()?;
+
+        // Select the card and put it into Transfer mode
+        self.card
+            .bus
+            .send_command(SDMMC_CMD7_SELECT_CARD_CMD.into(), (self.card.rca as u32) << 16)?;
+        let status = CardStatusRegister { val: self.card.bus.get_response()? };
+        if status.card_is_locked() {
+            // Only basic and lock card commands are accepted until the card is unlocked
+            self.select()?;
+            self.set_block_length()?;
+            self.card.state = State::Locked;
+            return Ok(());
+        }
+
+        // Get the SD card version
+        self.sd_acmd51()?;
+
+        if BusWidth::_4BIT <= self.card.bus.get_bus_width(self.slot)? {
+            self.card
+                .set_data_bus_width_to_4_bits()
+                .map_err(|_| MciError::Setup(SetupError::CouldNotSetBusWidth))?;
+            self.select()?;
+        }
+
+        // CMD6 is only supported from SD version 1.10
+        let version: usize = self.card.version.into();
+        if self.sd_set_uhs_mode()? {
+            // UHS-I bus speed mode selected
+        } else if version >= SdCardVersion::Sd1d10 as usize
+            && self
+                .card
+                .bus
+                .is_high_speed_capable()
+                .map_err(|_| MciError::Setup(SetupError::CouldNotCheckIfIsHighSpeed))?
+            && self
+                .set_to_high_speed_mode()
+                .map_err(|_| MciError::Setup(SetupError::CouldNotSetToHighSpeed))?
+        {
+            self.select()?;
+        }
+        self.set_block_length()?;
+        self.card.state = State::Ready;
+        Ok(())
+    }
+
+    /// Identify the card in the slot and initialize it accordingly
+    /// Tries CMD5 (SDIO), then CMD8 and ACMD41 (SD), then CMD1 (MMC) as recommended by the
+    /// specifications.
+    /// self.card.card_type is updated
+    pub fn probe(&mut self) -> Result<(), MciError> {
+        self.card.card_type.set_unknown();
+        // CMD0 - Reset all cards to idle state.
+        self.card.bus.send_command(SDMMC_MCI_CMD0_GO_IDLE_STATE.into(), 0)?;
+        let v2 = self.is_v2()?;
+        self.load_ocr_sdio()?;
+
+        let result = if self.card.card_type.sdio() && !self.card.card_type.sd() {
+            // SDIO card without memory, only an address is required
+            self.sdio_identify()
+        } else if self.load_ocr_sd_uhs(v2).is_ok() {
+            self.card.card_type.set_sd(true);
+            self.sd_identify()
+        } else if !v2 && !self.card.card_type.sdio() {
+            // Not a SD card, try MMC
+            self.card.bus.send_command(SDMMC_MCI_CMD0_GO_IDLE_STATE.into(), 0)?;
+            self.load_ocr_mmc().and_then(|_| {
+                self.card.card_type.set_mmc(true);
+                self.mmc_identify()
+            })
+        } else {
+            Err(MciError::UnusableCard)
+        };
+        if result.is_err() {
+            self.card.state = State::Unusable;
+        }
+        result
+    }
+
+    /// Identification process for SDIO card without memory
+    fn sdio_identify(&mut self) -> Result<(), MciError> {
+        // Ask the card to publish a new relative address (RCA)
+        self.card.bus.send_command(SD_CMD3_SEND_RELATIVE_ADDR.into(), 0)?;
+        self.card.rca = (self.card.bus.get_response()? >> 16) as u16;
+
+        // Select the card and put it into Transfer mode
+        self.card
+            .bus
+            .send_command(SDMMC_CMD7_SELECT_CARD_CMD.into(), (self.card.rca as u32) << 16)?;
+        self.select()?;
+        self.card.state = State::Ready;
+        Ok(())
+    }
+
+    /// CMD10 - Read again the card identification
+    /// The card is put in stand-by state to accept CMD10, then selected back
+    /// self.card.cid is updated
+    pub fn load_cid(&mut self) -> Result<&CidRegister, MciError> {
+        self.card.bus.send_command(SDMMC_CMD7_DESELECT_CARD_CMD.into(), 0)?;
+        let result = self.card.mci_load_cid();
+        self.card
+            .bus
+            .send_command(SDMMC_CMD7_SELECT_CARD_CMD.into(), (self.card.rca as u32) << 16)?;
+        result?;
+        Ok(&self.card.cid)
+    }
+
+    /// CMD16 - Set the block length to SD_MMC_BLOCK_SIZE
+    fn set_block_length(&mut self) -> Result<(), MciError> {
         for _ in 0..10 {
             // Retry is a workaround for no compliance card (Atmel Internal ref. MMC19)
             // These cards seem not ready immediately after the end of busy of mmc_cmd6_set_high_speed
@@ -1,21 +1,100 @@
+use bit_field::BitField;
 use embedded_error::mci::MciError;
+use embedded_error::ImplError;
+use embedded_hal::digital::v2::InputPin;
 
-use crate::bus::SpiBus;
-use crate::commands::SDMMC_MCI_CMD9_SEND_CSD;
-use crate::registers::csd::CsdRegister;
-
-use super::card::Card;
-
-impl<BUS: SpiBus> Card<BUS> {
-    /// CMD9: Card sends its card specific data (CSD)
-    /// self.mmc_card.csd is updated
-    pub fn sd_mmc_cmd9_spi(&mut self) -> Result<(), MciError> {
-        let cmd = SDMMC_MCI_CMD9_SEND_CSD.into();
-        let size = core::mem::size_of::<CsdRegister>();
-        self.mmc.adtc_start(cmd, (self.rca as u32) << 16, size as u16, 1, true)?;
-        // self.mmc_card.csd = CsdRegister {
-        //     val: self.mmc_card.mmc.get_response128()?,
-        // };
+use crate::bus::{SpiBus, SD_MMC_BLOCK_SIZE};
+use crate::card::version::{CardVersion, SdCardVersion};
+use crate::card::State;
+use crate::commands::{
+    SDMMC_CMD16_SET_BLOCKLEN, SDMMC_CMD55_APP_CMD, SDMMC_SPI_CMD0_GO_IDLE_STATE,
+    SDMMC_SPI_CMD58_READ_OCR, SDMMC_SPI_CMD59_CRC_ON_OFF, SD_SPI_ACMD41_SD_SEND_OP_COND,
+};
+use crate::registers::ocr::OcrRegister;
+
+use super::controller::Controller;
+
+// R1 response bit set while the card is in idle state
+const R1_IDLE: usize = 0;
+
+impl<BUS: SpiBus, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT> {
+    /// Initialize the SD card in SPI mode
+    /// This function runs the initialization procedure from power up, then it sets the card in
+    /// transfer state with 512 bytes block length.
+    /// self.card.card_type, self.card.version, self.card.csd, self.card.clock and
+    /// self.card.capacity are updated
+    pub fn init_spi(&mut self) -> Result<(), MciError> {
+        // Supply minimum of 74 clock cycles with CS high, then CS low
+        self.card.bus.init()?;
+
+        // R1 responses have the idle bit set until ACMD41 completes the initialization
+        self.card.bus.set_idle_accepted(true);
+        let v2 = self.activate_spi();
+        self.card.bus.set_idle_accepted(false);
+        let v2 = v2?;
+        self.card.card_type.set_sd(true);
+        self.card.version =
+            CardVersion::SdCard(if v2 { SdCardVersion::Sd2d0 } else { SdCardVersion::Sd1d0 });
+
+        // CMD59 - Turn CRC option off, CRC of data tokens is not computed by the bus.
+        // Ignored by the cards not supporting it.
+        self.card.bus.send_command(SDMMC_SPI_CMD59_CRC_ON_OFF.into(), 0).ok();
+
+        // CMD16 - Only effective for SDSC, SDHC and SDXC have fixed 512 bytes block length
+        self.card.bus.send_command(SDMMC_CMD16_SET_BLOCKLEN.into(), SD_MMC_BLOCK_SIZE as u32)?;
+
+        // Get the card identification and specific data
+        self.card.spi_load_cid()?;
+        self.card.spi_load_csd()?;
+        self.sd_decode_csd()?;
+        // SPI mode is limited to default speed
+        self.card.clock = self.card.clock.min(25_000_000);
+        self.select()?;
+        let locked = self.load_status()?.card_is_locked();
+        self.card.state = if locked { State::Locked } else { State::Ready };
+        Ok(())
+    }
+
+    /// Reset the card to idle state and enter SPI mode (CMD0), then activate the initialization
+    /// Returns whether it is a SD card V2
+    fn activate_spi(&mut self) -> Result<bool, MciError> {
+        // CMD0 - Reset card to idle state and enter SPI mode
+        self.card.bus.send_command(SDMMC_SPI_CMD0_GO_IDLE_STATE.into(), 0)?;
+        if !self.card.bus.get_response()?.get_bit(R1_IDLE) {
+            return Err(MciError::UnusableCard);
+        }
+
+        let v2 = self.is_v2()?;
+        self.load_ocr_spi(v2)?;
+        Ok(v2)
+    }
+
+    /// Activate the card initialization (ACMD41) until it leaves idle state, then read the OCR
+    /// (CMD58) to get the card capacity status
+    /// # Arguments
+    /// * `v2` Shall be true if it is a SD card V2
+    fn load_ocr_spi(&mut self, v2: bool) -> Result<(), MciError> {
+        // Timeout 1s = 400KHz / ((6+6+6+6)*8) cycles = 2100 retry
+        for i in (0..2100).rev() {
+            if i == 0 {
+                return Err(MciError::Impl(ImplError::TimedOut));
+            }
+            self.card.bus.send_command(SDMMC_CMD55_APP_CMD.into(), 0)?;
+            let mut arg = 0u32;
+            arg.set_bit(30, v2); // SD_ACMD41_HCS ACMD41 High Capacity Support
+            self.card.bus.send_command(SD_SPI_ACMD41_SD_SEND_OP_COND.into(), arg)?;
+            if !self.card.bus.get_response()?.get_bit(R1_IDLE) {
+                break;
+            }
+        }
+
+        if v2 {
+            self.card.bus.send_command(SDMMC_SPI_CMD58_READ_OCR.into(), 0)?;
+            let ocr = OcrRegister { val: self.card.bus.get_response()? };
+            if ocr.card_capacity_status() {
+                self.card.card_type.set_high_capacity(true);
+            }
+        }
         Ok(())
     }
 }
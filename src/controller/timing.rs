@@ -0,0 +1,326 @@
+use embedded_error::mci::MciError;
+use embedded_error::ImplError;
+use embedded_hal::digital::v2::InputPin;
+
+use crate::bus::{SdMmcBus, SignalVoltage, Timing};
+use crate::command_arguments::mmc::{Access, BusWidth, Cmd6};
+use crate::commands::{
+    MMC_CMD14_BUSTEST_R, MMC_CMD19_BUSTEST_W, MMC_CMD21_SEND_TUNING_BLOCK, MMC_CMD6_SWITCH,
+};
+use crate::mode_index::ModeIndex;
+
+use super::controller::Controller;
+use super::mmc::MMC_SWITCH_TIMEOUT_MS;
+
+// HS_TIMING values
+const HS_TIMING_LEGACY: u8 = 0;
+const HS_TIMING_HIGH_SPEED: u8 = 1;
+const HS_TIMING_HS200: u8 = 2;
+const HS_TIMING_HS400: u8 = 3;
+
+// BUS_WIDTH flag for HS400
+const BUS_WIDTH_ENHANCED_STROBE: u8 = 0x80;
+
+// Bus test patterns, the card sends back the inverted pattern on the first bits of each line
+const BUS_TEST_8BIT: [u8; 8] = [0x55, 0xAA, 0, 0, 0, 0, 0, 0];
+const BUS_TEST_4BIT: [u8; 4] = [0x5A, 0, 0, 0];
+
+pub const MMC_HS200_CLOCK: u32 = 200_000_000;
+
+/// Tuning block pattern for 4 bits bus
+pub const TUNING_BLOCK_4BIT: [u8; 64] = [
+    0xff, 0x0f, 0xff, 0x00, 0xff, 0xcc, 0xc3, 0xcc, 0xc3, 0x3c, 0xcc, 0xff, 0xfe, 0xff, 0xfe, 0xef,
+    0xff, 0xdf, 0xff, 0xdd, 0xff, 0xfb, 0xff, 0xfb, 0xbf, 0xff, 0x7f, 0xff, 0x77, 0xf7, 0xbd, 0xef,
+    0xff, 0xf0, 0xff, 0xf0, 0x0f, 0xfc, 0xcc, 0x3c, 0xcc, 0x33, 0xcc, 0xcf, 0xff, 0xef, 0xff, 0xee,
+    0xff, 0xfd, 0xff, 0xfd, 0xdf, 0xff, 0xbf, 0xff, 0xbb, 0xff, 0xf7, 0xff, 0xf7, 0x7f, 0x7b, 0xde,
+];
+
+/// Tuning block pattern for 8 bits bus
+pub const TUNING_BLOCK_8BIT: [u8; 128] = [
+    0xff, 0xff, 0x00, 0xff, 0xff, 0xff, 0x00, 0x00, 0xff, 0xff, 0xcc, 0xcc, 0xcc, 0x33, 0xcc, 0xcc,
+    0xcc, 0x33, 0x33, 0xcc, 0xcc, 0xcc, 0xff, 0xff, 0xff, 0xee, 0xff, 0xff, 0xff, 0xee, 0xee, 0xff,
+    0xff, 0xff, 0xdd, 0xff, 0xff, 0xff, 0xdd, 0xdd, 0xff, 0xff, 0xff, 0xbb, 0xff, 0xff, 0xff, 0xbb,
+    0xbb, 0xff, 0xff, 0xff, 0x77, 0xff, 0xff, 0xff, 0x77, 0x77, 0xff, 0x77, 0xbb, 0xdd, 0xee, 0xff,
+    0xff, 0xff, 0xff, 0x00, 0xff, 0xff, 0xff, 0x00, 0x00, 0xff, 0xff, 0xcc, 0xcc, 0xcc, 0x33, 0xcc,
+    0xcc, 0xcc, 0x33, 0x33, 0xcc, 0xcc, 0xcc, 0xff, 0xff, 0xff, 0xee, 0xff, 0xff, 0xff, 0xee, 0xee,
+    0xff, 0xff, 0xff, 0xdd, 0xff, 0xff, 0xff, 0xdd, 0xdd, 0xff, 0xff, 0xff, 0xbb, 0xff, 0xff, 0xff,
+    0xbb, 0xbb, 0xff, 0xff, 0xff, 0x77, 0xff, 0xff, 0xff, 0x77, 0x77, 0xff, 0x77, 0xbb, 0xdd, 0xee,
+];
+
+impl<BUS: SdMmcBus, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT> {
+    /// Switch to the fastest timing supported by both the card and the host among HS400 with
+    /// enhanced strobe, HS400 and HS200. The previous timing is restored on failure.
+    pub(crate) fn mmc_select_fastest_timing(&mut self) -> Result<(), MciError> {
+        let (timing, clock, voltage) =
+            (self.card.timing, self.card.clock, self.card.signal_voltage);
+        let selected = self
+            .mmc_set_hs400_enhanced_strobe()
+            .and_then(|done| if done { Ok(true) } else { self.mmc_set_hs400() })
+            .and_then(|done| if done { Ok(true) } else { self.mmc_set_hs200() });
+        if selected.is_ok() {
+            return Ok(());
+        }
+        let restored = self.mmc_restore_timing(timing, clock);
+        if self.card.signal_voltage != voltage {
+            self.card.bus.set_signal_voltage(voltage)?;
+            self.card.signal_voltage = voltage;
+        }
+        restored
+    }
+
+    /// CMD6 for MMC - Switch back to the single data rate `timing` after a failed selection
+    fn mmc_restore_timing(&mut self, timing: Timing, clock: u32) -> Result<(), MciError> {
+        if self.card.ext_csd.byte(ModeIndex::BusWidth) != u32::from(&self.card.bus_width) as u8 {
+            // Leave DDR with the timing of the DDR switch
+            self.mmc_switch_timing(HS_TIMING_HIGH_SPEED, Timing::HighSpeed, 52_000_000)?;
+            self.mmc_switch(ModeIndex::BusWidth, u32::from(&self.card.bus_width) as u8, 0)?;
+        }
+        let value =
+            if timing == Timing::HighSpeed { HS_TIMING_HIGH_SPEED } else { HS_TIMING_LEGACY };
+        self.mmc_switch_timing(value, timing, clock)
+    }
+
+    /// CMD6 for MMC - Select the widest bus width supported by both the card and the host, each
+    /// width is verified with the bus test procedure before use. Falls back to 1 bit bus width.
+    /// self.card.bus_width is updated
+    pub fn mmc_select_bus_width(&mut self) -> Result<BusWidth, MciError> {
+        let host_bus_width = self.card.bus.get_bus_width(self.slot)?;
+        for &bus_width in [BusWidth::_8BIT, BusWidth::_4BIT].iter() {
+            if bus_width > host_bus_width {
+                continue;
+            }
+            let switched = self.mmc_switch(ModeIndex::BusWidth, u32::from(&bus_width) as u8, 0);
+            if switched.is_ok() {
+                self.card.bus_width = bus_width;
+                self.select()?;
+                if self.mmc_bus_test().unwrap_or(false) {
+                    return Ok(bus_width);
+                }
+            }
+            // Bring the card back to transfer state after a rejected switch or a partial transfer
+            self.load_status().ok();
+        }
+        self.mmc_switch(ModeIndex::BusWidth, u32::from(&BusWidth::_1BIT) as u8, 0)?;
+        self.card.bus_width = BusWidth::_1BIT;
+        self.select()?;
+        Ok(BusWidth::_1BIT)
+    }
+
+    /// CMD19 and CMD14 for MMC - Write a test pattern then read it back inverted
+    fn mmc_bus_test(&mut self) -> Result<bool, MciError> {
+        let pattern: &[u8] = match self.card.bus_width {
+            BusWidth::_8BIT => &BUS_TEST_8BIT,
+            BusWidth::_4BIT => &BUS_TEST_4BIT,
+            _ => return Ok(true),
+        };
+        self.card.bus.adtc_start(MMC_CMD19_BUSTEST_W.into(), 0, pattern.len() as u16, 1, true)?;
+        self.card.bus.write_blocks(pattern)?;
+        self.card.bus.wait_until_write_finished()?;
+
+        let mut buf = [0u8; BUS_TEST_8BIT.len()];
+        let response = &mut buf[..pattern.len()];
+        self.card.bus.adtc_start(MMC_CMD14_BUSTEST_R.into(), 0, pattern.len() as u16, 1, true)?;
+        self.card.bus.read_blocks(response)?;
+        self.card.bus.wait_until_read_finished()?;
+        // Only the first byte of each line carries the pattern
+        let lines = pattern.len() / 4;
+        Ok(pattern.iter().zip(response.iter()).take(lines).all(|(p, r)| p ^ r == 0xFF))
+    }
+
+    /// CMD6 for MMC - Switch to DDR52 from high speed timing
+    /// Returns false if DDR52 is not supported by the card, the host or the bus width
+    /// self.card.timing is updated
+    pub fn mmc_set_ddr52(&mut self) -> Result<bool, MciError> {
+        if !self.card.ext_csd.ddr52_supported()
+            || self.card.timing != Timing::HighSpeed
+            || self.card.bus_width == BusWidth::_1BIT
+            || !self.card.bus.is_timing_supported(Timing::Ddr52)?
+        {
+            return Ok(false);
+        }
+        let bus_width = self.card.bus_width;
+        self.mmc_switch(ModeIndex::BusWidth, u32::from(&bus_width.ddr()) as u8, 0)?;
+        let clock = self.card.clock;
+        if let Err(error) = self.set_timing(Timing::Ddr52, clock) {
+            // Stay in SDR high speed
+            self.mmc_switch(ModeIndex::BusWidth, u32::from(&bus_width) as u8, 0)?;
+            self.set_timing(Timing::HighSpeed, clock).ok();
+            return Err(error);
+        }
+        Ok(true)
+    }
+
+    /// CMD6 for MMC - Switch to HS200 then tune the sampling point with CMD21
+    /// Returns false if HS200 is not supported by the card, the host or the bus width
+    /// self.card.timing and self.card.clock are updated
+    pub fn mmc_set_hs200(&mut self) -> Result<bool, MciError> {
+        if !self.card.ext_csd.hs200_supported()
+            || self.card.bus_width == BusWidth::_1BIT
+            || !self.card.bus.is_timing_supported(Timing::Hs200)?
+        {
+            return Ok(false);
+        }
+        self.card.bus.set_signal_voltage(SignalVoltage::_1V8)?;
+        self.card.signal_voltage = SignalVoltage::_1V8;
+        self.mmc_switch_timing(HS_TIMING_HS200, Timing::Hs200, MMC_HS200_CLOCK)?;
+        self.tune(MMC_CMD21_SEND_TUNING_BLOCK.into())?;
+        Ok(true)
+    }
+
+    /// CMD6 for MMC - Switch to HS400, the sampling point is tuned in HS200 beforehand
+    /// Returns false if HS400 is not supported by the card, the host or the bus width
+    /// self.card.timing and self.card.clock are updated
+    pub fn mmc_set_hs400(&mut self) -> Result<bool, MciError> {
+        if !self.card.ext_csd.hs400_supported()
+            || self.card.bus_width != BusWidth::_8BIT
+            || !self.card.bus.is_timing_supported(Timing::Hs400)?
+        {
+            return Ok(false);
+        }
+        if self.card.timing != Timing::Hs200 && !self.mmc_set_hs200()? {
+            return Ok(false);
+        }
+        // DDR bus width can only be selected in high speed timing
+        self.mmc_switch_timing(HS_TIMING_HIGH_SPEED, Timing::HighSpeed, 52_000_000)?;
+        self.mmc_switch(ModeIndex::BusWidth, u32::from(&BusWidth::_8BIT_DDR) as u8, 0)?;
+        self.mmc_switch_timing(HS_TIMING_HS400, Timing::Hs400, MMC_HS200_CLOCK)?;
+        Ok(true)
+    }
+
+    /// CMD6 for MMC - Switch to HS400 with enhanced strobe, no tuning is needed
+    /// Returns false if not supported by the card, the host or the bus width
+    /// self.card.timing and self.card.clock are updated
+    pub fn mmc_set_hs400_enhanced_strobe(&mut self) -> Result<bool, MciError> {
+        if !self.card.ext_csd.hs400_supported()
+            || !self.card.ext_csd.enhanced_strobe_supported()
+            || self.card.bus_width != BusWidth::_8BIT
+            || !self.card.bus.is_timing_supported(Timing::Hs400EnhancedStrobe)?
+        {
+            return Ok(false);
+        }
+        self.card.bus.set_signal_voltage(SignalVoltage::_1V8)?;
+        self.card.signal_voltage = SignalVoltage::_1V8;
+        if self.card.timing != Timing::HighSpeed {
+            self.mmc_switch_timing(HS_TIMING_HIGH_SPEED, Timing::HighSpeed, 52_000_000)?;
+        }
+        let bus_width = u32::from(&BusWidth::_8BIT_DDR) as u8 | BUS_WIDTH_ENHANCED_STROBE;
+        self.mmc_switch(ModeIndex::BusWidth, bus_width, 0)?;
+        self.mmc_switch_timing(HS_TIMING_HS400, Timing::Hs400EnhancedStrobe, MMC_HS200_CLOCK)?;
+        Ok(true)
+    }
+
+    /// CMD6 for MMC - Write HS_TIMING
+    /// The host timing is changed before checking the switch status with the new timing
+    fn mmc_switch_timing(&mut self, value: u8, timing: Timing, clock: u32) -> Result<(), MciError> {
+        let mut arg = Cmd6::default();
+        arg.set_access(Access::WriteByte).set_mode_index(ModeIndex::HsTimingIndex).set_value(value);
+        self.card.bus.send_command(MMC_CMD6_SWITCH.into(), arg.val)?;
+        self.set_timing(timing, clock)?;
+        let timeout_ms = self.card.ext_csd.generic_cmd6_time().max(MMC_SWITCH_TIMEOUT_MS);
+        if self.wait_ready(timeout_ms)?.switch_error() {
+            return Err(MciError::Impl(ImplError::InvalidConfiguration));
+        }
+        self.card.ext_csd.set_byte(ModeIndex::HsTimingIndex, value);
+        Ok(())
+    }
+
+    /// Apply a timing mode on the host then select the card again with the new clock
+    /// self.card.timing, self.card.high_speed and self.card.clock are updated
+    pub(crate) fn set_timing(&mut self, timing: Timing, clock: u32) -> Result<(), MciError> {
+        match self.card.bus.set_timing(timing) {
+            // Hosts without timing control only need the clock and bus mode of select_device
+            Err(MciError::Impl(ImplError::InvalidConfiguration))
+                if timing == Timing::Legacy || timing == Timing::HighSpeed => {}
+            result => result?,
+        }
+        self.card.timing = timing;
+        self.card.high_speed = timing != Timing::Legacy;
+        self.card.clock = clock;
+        self.select()
+    }
+
+    /// Tune the sampling point with the tuning block `command`, CMD19 for SD and CMD21 for MMC
+    /// The middle of the largest window of passing sampling points is selected
+    pub(crate) fn tune(&mut self, command: u32) -> Result<(), MciError> {
+        let points = self.card.bus.tuning_points();
+        if points == 0 {
+            return Ok(());
+        }
+        let pattern: &[u8] = if self.card.bus_width == BusWidth::_8BIT {
+            &TUNING_BLOCK_8BIT
+        } else {
+            &TUNING_BLOCK_4BIT
+        };
+        let (mut start, mut length) = (0u8, 0u8);
+        let (mut best_start, mut best_length) = (0u8, 0u8);
+        for point in 0..points {
+            self.card.bus.set_tuning_point(point)?;
+            if self.read_tuning_block(command, pattern).unwrap_or(false) {
+                if length == 0 {
+                    start = point;
+                }
+                length += 1;
+                if length > best_length {
+                    best_start = start;
+                    best_length = length;
+                }
+            } else {
+                length = 0;
+            }
+        }
+        if best_length == 0 {
+            return Err(MciError::ReadError);
+        }
+        self.card.bus.set_tuning_point(best_start + best_length / 2)
+    }
+
+    fn read_tuning_block(&mut self, command: u32, pattern: &[u8]) -> Result<bool, MciError> {
+        let mut buf = [0u8; TUNING_BLOCK_8BIT.len()];
+        let block = &mut buf[..pattern.len()];
+        self.card.bus.adtc_start(command, 0, pattern.len() as u16, 1, true)?;
+        self.card.bus.read_blocks(block)?;
+        self.card.bus.wait_until_read_finished()?;
+        Ok(block == pattern)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec;
+
+    use crate::controller::mock::{self, MockBus};
+    use crate::registers::ext_csd::EXT_CSD_CARD_TYPE_INDEX;
+
+    use super::*;
+
+    #[test]
+    fn set_timing_without_host_timing_control() {
+        let mut controller = mock::controller(MockBus::default(), true);
+        assert!(controller.set_timing(Timing::HighSpeed, 52_000_000).is_ok());
+        assert_eq!(controller.card.timing, Timing::HighSpeed);
+        assert!(controller.card.high_speed);
+        assert!(controller.set_timing(Timing::Legacy, 26_000_000).is_ok());
+        assert_eq!(controller.card.clock, 26_000_000);
+        assert!(matches!(
+            controller.set_timing(Timing::Hs200, MMC_HS200_CLOCK),
+            Err(MciError::Impl(ImplError::InvalidConfiguration))
+        ));
+        assert_eq!(controller.card.timing, Timing::Legacy);
+    }
+
+    #[test]
+    fn failed_hs200_restores_signal_voltage() {
+        let bus = MockBus { timings: vec![Timing::Hs200], tuning_points: 4, ..Default::default() };
+        let mut controller = mock::controller(bus, true);
+        controller.card.ext_csd.0[EXT_CSD_CARD_TYPE_INDEX] = 0x10;
+        controller.card.ext_csd.set_byte(ModeIndex::BusWidth, u32::from(&BusWidth::_4BIT) as u8);
+        assert!(controller.mmc_select_fastest_timing().is_ok());
+        assert_eq!(controller.card.bus.signal_voltage, Some(SignalVoltage::_3V3));
+        assert_eq!(controller.card.signal_voltage, SignalVoltage::_3V3);
+        assert_eq!(controller.card.timing, Timing::Legacy);
+        assert_eq!(controller.card.clock, 25_000_000);
+        assert_eq!(controller.card.ext_csd.byte(ModeIndex::HsTimingIndex), HS_TIMING_LEGACY);
+    }
+}
@@ -0,0 +1,114 @@
+use embedded_error::mci::MciError;
+use embedded_hal::digital::v2::InputPin;
+
+use crate::bus::{SdMmcBus, SignalVoltage, Timing};
+use crate::command_arguments::mmc::BusWidth;
+use crate::command_arguments::sd::cmd6::{
+    BusSpeedMode, Cmd6, Cmd6Mode, CurrentLimit, DriverStrength,
+};
+use crate::commands::{SD_CMD11_VOLTAGE_SWITCH, SD_CMD19_SEND_TUNING_BLOCK, SD_CMD6_SWITCH_FUNC};
+
+use super::controller::Controller;
+
+// Fastest first
+const BUS_SPEED_MODES: [(BusSpeedMode, Timing, u32); 4] = [
+    (BusSpeedMode::Sdr104, Timing::UhsSdr104, 208_000_000),
+    (BusSpeedMode::Ddr50, Timing::UhsDdr50, 50_000_000),
+    (BusSpeedMode::Sdr50, Timing::UhsSdr50, 100_000_000),
+    (BusSpeedMode::Sdr25, Timing::UhsSdr25, 50_000_000),
+];
+
+impl<BUS: SdMmcBus, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT> {
+    /// Ask all cards to send their operations conditions, 1.8V signaling is requested when the
+    /// host supports UHS-I then switched with CMD11 if accepted by the card
+    /// self.card.signal_voltage is updated
+    pub fn load_ocr_sd_uhs(&mut self, v2: bool) -> Result<(), MciError> {
+        let s18r = v2 && self.card.bus.is_timing_supported(Timing::UhsSdr12)?;
+        if self.load_ocr_sdcard_1v8(v2, s18r)? {
+            self.sd_switch_voltage()?;
+        }
+        Ok(())
+    }
+
+    /// CMD11 for SD - Switch the signaling to 1.8V, the card shall be powered cycled on failure
+    /// self.card.signal_voltage is updated
+    pub fn sd_switch_voltage(&mut self) -> Result<(), MciError> {
+        self.card.bus.send_command(SD_CMD11_VOLTAGE_SWITCH.into(), 0)?;
+        self.card.bus.set_signal_voltage(SignalVoltage::_1V8)?;
+        self.card.signal_voltage = SignalVoltage::_1V8;
+        Ok(())
+    }
+
+    /// CMD6 for SD - Switch to the fastest UHS-I bus speed mode supported by both the card and
+    /// the host, then tune the sampling point with CMD19 for SDR50 and SDR104.
+    /// Requires 1.8V signaling and 4 bits bus width.
+    /// Returns false if no UHS-I bus speed mode can be used, the card is then put back in default
+    /// speed if the switch failed
+    /// self.card.timing and self.card.clock are updated
+    pub fn sd_set_uhs_mode(&mut self) -> Result<bool, MciError> {
+        if self.card.signal_voltage != SignalVoltage::_1V8 || self.card.bus_width != BusWidth::_4BIT
+        {
+            return Ok(false);
+        }
+        let (timing, clock) = (self.card.timing, self.card.clock);
+        if let Ok(selected) = self.sd_select_uhs_mode() {
+            return Ok(selected);
+        }
+        // SDR12 is the default speed at 1.8V signaling
+        let mut arg = Cmd6::no_influence(Cmd6Mode::Switch);
+        arg.set_function_group1(BusSpeedMode::Sdr12 as u8);
+        self.cmd6(SD_CMD6_SWITCH_FUNC, arg)?;
+        self.card.bus.send_clock()?;
+        self.set_timing(timing, clock)?;
+        Ok(false)
+    }
+
+    fn sd_select_uhs_mode(&mut self) -> Result<bool, MciError> {
+        let supported = self.sd_function_support()?.group1_function_support();
+        let strength = self.card.bus.driver_strength();
+        if strength != DriverStrength::TypeB {
+            // Type B is used if the card does not support the driver strength of the board
+            self.sd_set_driver_strength(strength)?;
+        }
+
+        for &(mode, timing, clock) in BUS_SPEED_MODES.iter() {
+            if supported & (1 << mode as u16) == 0 || !self.card.bus.is_timing_supported(timing)? {
+                continue;
+            }
+            let mut arg = Cmd6::no_influence(Cmd6Mode::Switch);
+            arg.set_function_group1(mode as u8);
+            let status = self.cmd6(SD_CMD6_SWITCH_FUNC, arg)?;
+            if status.group1_rc() != mode as u8 {
+                continue;
+            }
+            if status.group1_busy() > 0 {
+                return Err(MciError::GroupBusy);
+            }
+            // CMD6 function switching period is within 8 clocks after then bit of status data
+            self.card.bus.send_clock()?;
+            if mode != BusSpeedMode::Sdr25 {
+                self.sd_select_current_limit()?;
+            }
+            self.set_timing(timing, clock)?;
+            if mode == BusSpeedMode::Sdr104 || mode == BusSpeedMode::Sdr50 {
+                self.tune(SD_CMD19_SEND_TUNING_BLOCK.into())?;
+            }
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// CMD6 for SD - Select the highest current limit supported by both the card and the host
+    fn sd_select_current_limit(&mut self) -> Result<(), MciError> {
+        let max_current = self.card.bus.max_current();
+        let supported = self.sd_function_support()?.group4_info_status();
+        let limits = [CurrentLimit::_800mA, CurrentLimit::_600mA, CurrentLimit::_400mA];
+        for &limit in limits.iter() {
+            if limit.milliamps() <= max_current && supported & (1 << limit as u16) != 0 {
+                self.sd_set_current_limit(limit)?;
+                break;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use embedded_error::mci::MciError;
+use embedded_error::ImplError;
+use embedded_hal::digital::v2::InputPin;
+
+use crate::bus::{Adtc, Bus, Read, Write};
+use crate::commands::{
+    SDMMC_CMD28_SET_WRITE_PROT, SDMMC_CMD29_CLR_WRITE_PROT, SDMMC_CMD30_SEND_WRITE_PROT,
+};
+
+use super::controller::Controller;
+
+impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Controller<BUS, WP, DETECT> {
+    /// Write protect group size in blocks, None if write protect groups are not supported
+    pub fn write_protect_group_size(&self) -> Option<u32> {
+        let csd = &self.card.csd;
+        if !csd.write_protect_group_enable() {
+            return None;
+        }
+        let ext_csd = &self.card.ext_csd;
+        if self.card.card_type.mmc() && ext_csd.erase_group_def() {
+            // In high capacity erase groups of 512 KBytes
+            Some(ext_csd.hc_write_protect_group_size() as u32 * self.erase_group_size())
+        } else if self.card.card_type.mmc() {
+            Some((csd.mmc_write_protect_group_size() as u32 + 1) * self.erase_group_size())
+        } else {
+            // In SECTOR_SIZE units, regardless of ERASE_BLK_EN
+            Some((csd.sd_write_protect_group_size() as u32 + 1) * (csd.sd_sector_size() as u32 + 1))
+        }
+    }
+
+    /// CMD28 - Set write protection of the groups covering `num_blocks` blocks from `start`
+    pub fn set_write_protect(&mut self, start: u32, num_blocks: u32) -> Result<(), MciError> {
+        self.write_protect_groups(start, num_blocks, true)
+    }
+
+    /// CMD29 - Clear write protection of the groups covering `num_blocks` blocks from `start`
+    pub fn clear_write_protect(&mut self, start: u32, num_blocks: u32) -> Result<(), MciError> {
+        self.write_protect_groups(start, num_blocks, false)
+    }
+
+    fn write_protect_groups(
+        &mut self,
+        start: u32,
+        num_blocks: u32,
+        protect: bool,
+    ) -> Result<(), MciError> {
+        let group_size = self
+            .write_protect_group_size()
+            .ok_or(MciError::Impl(ImplError::InvalidConfiguration))?;
+        if !start.is_multiple_of(group_size) || !num_blocks.is_multiple_of(group_size) {
+            return Err(MciError::Impl(ImplError::InvalidConfiguration));
+        }
+        self.select()?;
+        let cmd: u32 = if protect {
+            SDMMC_CMD28_SET_WRITE_PROT.into()
+        } else {
+            SDMMC_CMD29_CLR_WRITE_PROT.into()
+        };
+        for group in 0..num_blocks / group_size {
+            self.load_status()?;
+            let arg = self.block_address(start + group * group_size);
+            self.card.bus.send_command(cmd, arg)?;
+            let status = self.load_status()?;
+            if status.has_error() {
+                return Err(MciError::WriteError);
+            }
+        }
+        Ok(())
+    }
+
+    /// CMD30 - Write protection bitmap of the 32 groups from the group of `start` block
+    /// Bit 0 is the status of the first group, set if the group is write protected
+    pub fn write_protect_status(&mut self, start: u32) -> Result<u32, MciError> {
+        self.write_protect_group_size().ok_or(MciError::Impl(ImplError::InvalidConfiguration))?;
+        self.select()?;
+        self.load_status()?;
+        let mut buf = [0u8; 4];
+        let arg = self.block_address(start);
+        self.card.bus.adtc_start(SDMMC_CMD30_SEND_WRITE_PROT.into(), arg, 4, 1, true)?;
+        self.card.bus.read_blocks(&mut buf)?;
+        self.card.bus.wait_until_read_finished()?;
+        Ok(u32::from_be_bytes(buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use crate::controller::mock::{self, MockBus};
+    use crate::mode_index::ModeIndex;
+    use crate::registers::ext_csd::{
+        EXT_CSD_HC_ERASE_GRP_SIZE_INDEX, EXT_CSD_HC_WP_GRP_SIZE_INDEX,
+    };
+
+    #[test]
+    fn mmc_legacy_write_protect_groups() {
+        let mut controller = mock::controller(MockBus::default(), true);
+        assert_eq!(controller.write_protect_group_size(), None);
+        controller.card.csd.set_write_protect_group_enable(true);
+        controller.card.csd.set_mmc_erase_group_size(1);
+        controller.card.csd.set_mmc_write_protect_group_size(3);
+        assert_eq!(controller.write_protect_group_size(), Some(4 * 2));
+    }
+
+    #[test]
+    fn mmc_high_capacity_write_protect_groups() {
+        let mut controller = mock::controller(MockBus::default(), true);
+        controller.card.csd.set_write_protect_group_enable(true);
+        controller.card.csd.set_mmc_write_protect_group_size(3);
+        controller.card.ext_csd.set_byte(ModeIndex::EraseGroupDef, 1);
+        controller.card.ext_csd.0[EXT_CSD_HC_ERASE_GRP_SIZE_INDEX] = 2;
+        controller.card.ext_csd.0[EXT_CSD_HC_WP_GRP_SIZE_INDEX] = 3;
+        assert_eq!(controller.write_protect_group_size(), Some(3 * 2048));
+        assert!(controller.set_write_protect(6144, 2 * 6144).is_ok());
+        let commands = controller.card.bus.commands.borrow();
+        let protected: std::vec::Vec<u32> =
+            commands.iter().filter(|&&(index, _)| index == 28).map(|&(_, arg)| arg).collect();
+        assert_eq!(protected, [6144, 12288]);
+    }
+}
@@ -1,3 +1,7 @@
+use core::fmt;
+
+use embedded_error::mci::MciError;
+
 pub enum SdMmcError {
     InitOngoing = 1,
     NoCard = 2,
@@ -7,3 +11,59 @@ pub enum SdMmcError {
     IllegalParameter = 6,
     WriteProtected = 7,
 }
+
+/// Failed block write, with the number of blocks known to be written from the start address
+/// The write can be resumed at `start + written`. Failed reads report 0 blocks written.
+pub struct WriteBlocksError {
+    pub error: MciError,
+    pub written: u32,
+}
+
+impl From<MciError> for WriteBlocksError {
+    fn from(error: MciError) -> Self {
+        WriteBlocksError { error, written: 0 }
+    }
+}
+
+impl fmt::Debug for WriteBlocksError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let error = match self.error {
+            MciError::DataError(_) => "DataError",
+            MciError::CommandInhibited => "CommandInhibited",
+            MciError::CommandError(_) => "CommandError",
+            MciError::Adma => "Adma",
+            MciError::GroupBusy => "GroupBusy",
+            MciError::CiaCouldNotFindTuple => "CiaCouldNotFindTuple",
+            MciError::IncorrectDataSize => "IncorrectDataSize",
+            MciError::CouldNotSelectDevice => "CouldNotSelectDevice",
+            MciError::NoCard => "NoCard",
+            MciError::UnusableCard => "UnusableCard",
+            MciError::ReadError => "ReadError",
+            MciError::WriteProtected => "WriteProtected",
+            MciError::WriteError => "WriteError",
+            MciError::PinLevelReadError => "PinLevelReadError",
+            MciError::Setup(_) => "Setup",
+            MciError::Impl(_) => "Impl",
+            // MciError is non exhaustive
+            _ => "Unknown",
+        };
+        f.debug_struct("WriteBlocksError")
+            .field("error", &format_args!("{}", error))
+            .field("written", &self.written)
+            .finish()
+    }
+}
+
+impl fmt::Display for WriteBlocksError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl core::error::Error for WriteBlocksError {}
+
+impl From<WriteBlocksError> for MciError {
+    fn from(error: WriteBlocksError) -> Self {
+        error.error
+    }
+}
@@ -1,5 +1,7 @@
 #![no_std]
 #![allow(deprecated)]
+#[cfg(feature = "embedded-sdmmc")]
+pub mod block_device;
 pub mod bus;
 pub mod card;
 pub mod command_arguments;
@@ -11,7 +13,10 @@ pub mod dummy_input_pin;
 pub mod error;
 pub mod mode_index;
 pub mod registers;
+pub mod rpmb;
 pub mod sd;
 #[cfg(feature = "sdio")]
 pub mod sdio_state;
+#[cfg(feature = "embedded-storage")]
+pub mod storage;
 pub mod transaction;
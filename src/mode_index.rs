@@ -1,6 +1,12 @@
 use core::hint::unreachable_unchecked;
 
+#[derive(Copy, Clone)]
 pub enum ModeIndex {
+    FlushCache = 0x20,
+    CacheCtrl = 0x21,
+    PowerOffNotification = 0x22,
+    Sanitize = 0xA5,
+    BootWp = 0xAD,
     EraseGroupDef = 0xAF,
     BootBusWidth = 0xB1,
     BootConfig = 0xB3,
@@ -15,6 +21,11 @@ pub enum ModeIndex {
 impl From<u32> for ModeIndex {
     fn from(val: u32) -> Self {
         match val {
+            0x20 => ModeIndex::FlushCache,
+            0x21 => ModeIndex::CacheCtrl,
+            0x22 => ModeIndex::PowerOffNotification,
+            0xA5 => ModeIndex::Sanitize,
+            0xAD => ModeIndex::BootWp,
             0xAF => ModeIndex::EraseGroupDef,
             0xB1 => ModeIndex::BootBusWidth,
             0xB3 => ModeIndex::BootConfig,
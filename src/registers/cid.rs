@@ -0,0 +1,200 @@
+use bit_field::BitArray;
+
+/// Card identification register, SD and MMC share the manufacturer ID position only
+#[derive(Default, Copy, Clone, PartialEq)]
+pub struct CidRegister(pub [u32; 4]);
+
+impl CidRegister {
+    fn chars<const N: usize>(&self, msb: usize) -> [u8; N] {
+        let mut name = [0u8; N];
+        for (i, c) in name.iter_mut().enumerate() {
+            let lsb = msb - 8 * (i + 1);
+            *c = self.0.get_bits(lsb..lsb + 8) as u8;
+        }
+        name
+    }
+
+    fn set_chars(&mut self, msb: usize, chars: &[u8]) {
+        for (i, &c) in chars.iter().enumerate() {
+            let lsb = msb - 8 * (i + 1);
+            self.0.set_bits(lsb..lsb + 8, c as u32);
+        }
+    }
+
+    pub fn set_manufacturer_id(&mut self, id: u8) {
+        self.0.set_bits(120..128, id as u32);
+    }
+
+    pub fn manufacturer_id(&self) -> u8 {
+        self.0.get_bits(120..128) as u8
+    }
+
+    pub fn set_sd_oem_id(&mut self, id: [u8; 2]) {
+        self.set_chars(120, &id);
+    }
+
+    /// OEM/Application ID - SD card, 2 ASCII characters
+    pub fn sd_oem_id(&self) -> [u8; 2] {
+        self.chars(120)
+    }
+
+    pub fn set_sd_product_name(&mut self, name: [u8; 5]) {
+        self.set_chars(104, &name);
+    }
+
+    /// Product name - SD card, 5 ASCII characters
+    pub fn sd_product_name(&self) -> [u8; 5] {
+        self.chars(104)
+    }
+
+    pub fn set_sd_product_revision(&mut self, revision: u8) {
+        self.0.set_bits(56..64, revision as u32);
+    }
+
+    /// Product revision - SD card, BCD coded n.m
+    pub fn sd_product_revision(&self) -> u8 {
+        self.0.get_bits(56..64) as u8
+    }
+
+    pub fn set_sd_serial_number(&mut self, serial: u32) {
+        self.0.set_bits(24..56, serial);
+    }
+
+    pub fn sd_serial_number(&self) -> u32 {
+        self.0.get_bits(24..56)
+    }
+
+    /// Year is saturated to 2000..=2255, month to 12
+    pub fn set_sd_manufacturing_date(&mut self, year: u16, month: u8) {
+        self.0.set_bits(12..20, year.saturating_sub(2000).min(255) as u32);
+        self.0.set_bits(8..12, month.min(12) as u32);
+    }
+
+    pub fn sd_manufacturing_year(&self) -> u16 {
+        2000 + self.0.get_bits(12..20) as u16
+    }
+
+    pub fn sd_manufacturing_month(&self) -> u8 {
+        self.0.get_bits(8..12) as u8
+    }
+
+    pub fn set_mmc_card_bga(&mut self, bga: u8) {
+        self.0.set_bits(112..114, bga as u32);
+    }
+
+    /// Device type - MMC card, 0: removable, 1: BGA, 2: POP
+    pub fn mmc_card_bga(&self) -> u8 {
+        self.0.get_bits(112..114) as u8
+    }
+
+    pub fn set_mmc_oem_id(&mut self, id: u8) {
+        self.0.set_bits(104..112, id as u32);
+    }
+
+    pub fn mmc_oem_id(&self) -> u8 {
+        self.0.get_bits(104..112) as u8
+    }
+
+    pub fn set_mmc_product_name(&mut self, name: [u8; 6]) {
+        self.set_chars(104, &name);
+    }
+
+    /// Product name - MMC card, 6 ASCII characters
+    pub fn mmc_product_name(&self) -> [u8; 6] {
+        self.chars(104)
+    }
+
+    pub fn set_mmc_product_revision(&mut self, revision: u8) {
+        self.0.set_bits(48..56, revision as u32);
+    }
+
+    /// Product revision - MMC card, BCD coded n.m
+    pub fn mmc_product_revision(&self) -> u8 {
+        self.0.get_bits(48..56) as u8
+    }
+
+    pub fn set_mmc_serial_number(&mut self, serial: u32) {
+        self.0.set_bits(16..48, serial);
+    }
+
+    pub fn mmc_serial_number(&self) -> u32 {
+        self.0.get_bits(16..48)
+    }
+
+    /// Year is saturated to 1997..=2025 and encoded modulo 16, years from 2013 are only decoded
+    /// for devices with EXT_CSD revision above 4. Month is saturated to 12.
+    pub fn set_mmc_manufacturing_date(&mut self, year: u16, month: u8) {
+        self.0.set_bits(8..12, ((year.clamp(1997, 2025) - 1997) % 16) as u32);
+        self.0.set_bits(12..16, month.min(12) as u32);
+    }
+
+    /// Manufacturing year - MMC card, from 1997 to 2012 up to EXT_CSD revision 4, from 2010 to
+    /// 2025 above
+    pub fn mmc_manufacturing_year(&self, ext_csd_revision: u8) -> u16 {
+        let year = 1997 + self.0.get_bits(8..12) as u16;
+        if ext_csd_revision > 4 && year < 2010 {
+            year + 16
+        } else {
+            year
+        }
+    }
+
+    pub fn mmc_manufacturing_month(&self) -> u8 {
+        self.0.get_bits(12..16) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sd_fields() {
+        // MID 0x03, OID "SD", PNM "SU08G", PRV 8.0, PSN 0x12345678, MDT 2015/06
+        let cid = CidRegister([0x7800_F601, 0x8012_3456, 0x5530_3847, 0x0353_4453]);
+        assert_eq!(cid.manufacturer_id(), 0x03);
+        assert_eq!(cid.sd_oem_id(), *b"SD");
+        assert_eq!(cid.sd_product_name(), *b"SU08G");
+        assert_eq!(cid.sd_product_revision(), 0x80);
+        assert_eq!(cid.sd_serial_number(), 0x1234_5678);
+        assert_eq!(cid.sd_manufacturing_year(), 2015);
+        assert_eq!(cid.sd_manufacturing_month(), 6);
+    }
+
+    #[test]
+    fn sd_manufacturing_date_saturates() {
+        let mut cid = CidRegister([0; 4]);
+        cid.set_sd_manufacturing_date(2023, 11);
+        assert_eq!(cid.0[0], 0x0001_7B00);
+        cid.set_sd_manufacturing_date(1990, 13);
+        assert_eq!(cid.sd_manufacturing_year(), 2000);
+        assert_eq!(cid.sd_manufacturing_month(), 12);
+        cid.set_sd_manufacturing_date(2300, 1);
+        assert_eq!(cid.sd_manufacturing_year(), 2255);
+    }
+
+    #[test]
+    fn mmc_manufacturing_date() {
+        let mut cid = CidRegister([0; 4]);
+        cid.set_mmc_manufacturing_date(2005, 3);
+        assert_eq!(cid.0[0], 0x0000_3800);
+        assert_eq!(cid.mmc_manufacturing_year(4), 2005);
+        assert_eq!(cid.mmc_manufacturing_month(), 3);
+        cid.set_mmc_manufacturing_date(1990, 13);
+        assert_eq!(cid.mmc_manufacturing_year(4), 1997);
+        assert_eq!(cid.mmc_manufacturing_month(), 12);
+    }
+
+    #[test]
+    fn mmc_manufacturing_year_after_2012() {
+        let mut cid = CidRegister([0; 4]);
+        cid.set_mmc_manufacturing_date(2013, 1);
+        assert_eq!(cid.0[0], 0x0000_1000);
+        assert_eq!(cid.mmc_manufacturing_year(4), 1997);
+        assert_eq!(cid.mmc_manufacturing_year(5), 2013);
+        cid.set_mmc_manufacturing_date(2011, 1);
+        assert_eq!(cid.mmc_manufacturing_year(8), 2011);
+        cid.set_mmc_manufacturing_date(2030, 1);
+        assert_eq!(cid.mmc_manufacturing_year(8), 2025);
+    }
+}
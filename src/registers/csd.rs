@@ -118,4 +118,66 @@ impl CsdRegister {
     pub fn card_size_multiplier(&self) -> u8 {
         self.0.get_bits(47..50) as u8
     }
+
+    /// Erase single block enable - SD card
+    pub fn set_sd_erase_block_enable(&mut self, enabled: bool) {
+        self.0.set_bits(46..47, enabled as u32);
+    }
+
+    pub fn sd_erase_block_enable(&self) -> bool {
+        self.0.get_bits(46..47) > 0
+    }
+
+    /// Erase sector size minus 1, in write blocks - SD card
+    pub fn set_sd_sector_size(&mut self, size: u8) {
+        self.0.set_bits(39..46, size as u32);
+    }
+
+    pub fn sd_sector_size(&self) -> u8 {
+        self.0.get_bits(39..46) as u8
+    }
+
+    /// Erase group size minus 1 - MMC card
+    pub fn set_mmc_erase_group_size(&mut self, size: u8) {
+        self.0.set_bits(42..47, size as u32);
+    }
+
+    pub fn mmc_erase_group_size(&self) -> u8 {
+        self.0.get_bits(42..47) as u8
+    }
+
+    /// Erase group size multiplier minus 1 - MMC card
+    pub fn set_mmc_erase_group_multiplier(&mut self, multiplier: u8) {
+        self.0.set_bits(37..42, multiplier as u32);
+    }
+
+    pub fn mmc_erase_group_multiplier(&self) -> u8 {
+        self.0.get_bits(37..42) as u8
+    }
+
+    /// Write protect group size minus 1, in erase sectors - SD card
+    pub fn set_sd_write_protect_group_size(&mut self, size: u8) {
+        self.0.set_bits(32..39, size as u32);
+    }
+
+    pub fn sd_write_protect_group_size(&self) -> u8 {
+        self.0.get_bits(32..39) as u8
+    }
+
+    /// Write protect group size minus 1, in erase groups - MMC card
+    pub fn set_mmc_write_protect_group_size(&mut self, size: u8) {
+        self.0.set_bits(32..37, size as u32);
+    }
+
+    pub fn mmc_write_protect_group_size(&self) -> u8 {
+        self.0.get_bits(32..37) as u8
+    }
+
+    pub fn set_write_protect_group_enable(&mut self, enabled: bool) {
+        self.0.set_bits(31..32, enabled as u32);
+    }
+
+    pub fn write_protect_group_enable(&self) -> bool {
+        self.0.get_bits(31..32) > 0
+    }
 }
@@ -0,0 +1,476 @@
+use bit_field::BitField;
+
+use crate::mode_index::ModeIndex;
+
+pub const EXT_CSD_SIZE: usize = 512;
+
+pub const EXT_CSD_GP_SIZE_MULT_INDEX: usize = 143;
+pub const EXT_CSD_PARTITION_SETTING_COMPLETED_INDEX: usize = 155;
+pub const EXT_CSD_PARTITIONING_SUPPORT_INDEX: usize = 160;
+pub const EXT_CSD_HPI_MGMT_INDEX: usize = 161;
+pub const EXT_CSD_BKOPS_EN_INDEX: usize = 163;
+pub const EXT_CSD_RPMB_SIZE_MULT_INDEX: usize = 168;
+pub const EXT_CSD_BOOT_WP_STATUS_INDEX: usize = 174;
+pub const EXT_CSD_STROBE_SUPPORT_INDEX: usize = 184;
+pub const EXT_CSD_REV_INDEX: usize = 192;
+pub const EXT_CSD_CARD_TYPE_INDEX: usize = 196;
+pub const EXT_CSD_DRIVER_STRENGTH_INDEX: usize = 197;
+pub const EXT_CSD_OUT_OF_INTERRUPT_TIME_INDEX: usize = 198;
+pub const EXT_CSD_PARTITION_SWITCH_TIME_INDEX: usize = 199;
+pub const EXT_CSD_PWR_CL_52_195_INDEX: usize = 200;
+pub const EXT_CSD_PWR_CL_26_195_INDEX: usize = 201;
+pub const EXT_CSD_PWR_CL_52_360_INDEX: usize = 202;
+pub const EXT_CSD_PWR_CL_26_360_INDEX: usize = 203;
+pub const EXT_CSD_SEC_COUNT_INDEX: usize = 212;
+pub const EXT_CSD_SLEEP_NOTIFICATION_TIME_INDEX: usize = 216;
+pub const EXT_CSD_S_A_TIMEOUT_INDEX: usize = 217;
+pub const EXT_CSD_HC_WP_GRP_SIZE_INDEX: usize = 221;
+pub const EXT_CSD_REL_WR_SEC_C_INDEX: usize = 222;
+pub const EXT_CSD_ERASE_TIMEOUT_MULT_INDEX: usize = 223;
+pub const EXT_CSD_HC_ERASE_GRP_SIZE_INDEX: usize = 224;
+pub const EXT_CSD_BOOT_SIZE_MULT_INDEX: usize = 226;
+pub const EXT_CSD_SEC_TRIM_MULT_INDEX: usize = 229;
+pub const EXT_CSD_SEC_ERASE_MULT_INDEX: usize = 230;
+pub const EXT_CSD_SEC_FEATURE_SUPPORT_INDEX: usize = 231;
+pub const EXT_CSD_TRIM_MULT_INDEX: usize = 232;
+pub const EXT_CSD_PWR_CL_200_195_INDEX: usize = 236;
+pub const EXT_CSD_PWR_CL_200_360_INDEX: usize = 237;
+pub const EXT_CSD_PWR_CL_DDR_52_195_INDEX: usize = 238;
+pub const EXT_CSD_PWR_CL_DDR_52_360_INDEX: usize = 239;
+pub const EXT_CSD_BKOPS_STATUS_INDEX: usize = 246;
+pub const EXT_CSD_POWER_OFF_LONG_TIME_INDEX: usize = 247;
+pub const EXT_CSD_GENERIC_CMD6_TIME_INDEX: usize = 248;
+pub const EXT_CSD_CACHE_SIZE_INDEX: usize = 249;
+pub const EXT_CSD_PWR_CL_DDR_200_360_INDEX: usize = 253;
+pub const EXT_CSD_PRE_EOL_INFO_INDEX: usize = 267;
+pub const EXT_CSD_DEVICE_LIFE_TIME_EST_TYP_A_INDEX: usize = 268;
+pub const EXT_CSD_DEVICE_LIFE_TIME_EST_TYP_B_INDEX: usize = 269;
+pub const EXT_CSD_BKOPS_SUPPORT_INDEX: usize = 502;
+pub const EXT_CSD_HPI_FEATURES_INDEX: usize = 503;
+pub const EXT_CSD_DATA_SECTOR_SIZE_INDEX: usize = 61;
+
+/// Extended CSD register of MMC 4.0 and higher, 512 bytes
+/// Byte N of the register is at index N
+#[derive(Copy, Clone)]
+pub struct ExtCsdRegister(pub [u8; EXT_CSD_SIZE]);
+
+impl Default for ExtCsdRegister {
+    fn default() -> Self {
+        ExtCsdRegister([0u8; EXT_CSD_SIZE])
+    }
+}
+
+impl ExtCsdRegister {
+    fn word(&self, index: usize, size: usize) -> u32 {
+        let mut word = 0u32;
+        for i in (0..size).rev() {
+            word = (word << 8) | self.0[index + i] as u32;
+        }
+        word
+    }
+
+    /// Update a byte after a successful CMD6 write access
+    pub fn set_byte(&mut self, index: ModeIndex, value: u8) {
+        self.0[index as usize] = value;
+    }
+
+    pub fn byte(&self, index: ModeIndex) -> u8 {
+        self.0[index as usize]
+    }
+
+    /// Extended CSD revision, 5: eMMC 4.41, 6: eMMC 4.5, 7: eMMC 5.0, 8: eMMC 5.1
+    pub fn revision(&self) -> u8 {
+        self.0[EXT_CSD_REV_INDEX]
+    }
+
+    /// Device type, bus speed modes supported
+    pub fn card_type(&self) -> u8 {
+        self.0[EXT_CSD_CARD_TYPE_INDEX]
+    }
+
+    pub fn hs26_supported(&self) -> bool {
+        self.card_type().get_bit(0)
+    }
+
+    pub fn hs52_supported(&self) -> bool {
+        self.card_type().get_bit(1)
+    }
+
+    /// DDR52 at 1.8V or 3V
+    pub fn ddr52_supported(&self) -> bool {
+        self.card_type().get_bit(2)
+    }
+
+    /// HS200 at 1.8V
+    pub fn hs200_supported(&self) -> bool {
+        self.card_type().get_bit(4)
+    }
+
+    /// HS400 at 1.8V
+    pub fn hs400_supported(&self) -> bool {
+        self.card_type().get_bit(6)
+    }
+
+    pub fn enhanced_strobe_supported(&self) -> bool {
+        self.0[EXT_CSD_STROBE_SUPPORT_INDEX].get_bit(0)
+    }
+
+    /// Driver strength types supported, bit N for type N
+    pub fn driver_strength(&self) -> u8 {
+        self.0[EXT_CSD_DRIVER_STRENGTH_INDEX]
+    }
+
+    /// Number of 512 bytes sectors, capacity of high capacity devices
+    pub fn sector_count(&self) -> u32 {
+        self.word(EXT_CSD_SEC_COUNT_INDEX, 4)
+    }
+
+    /// Data sector size in bytes, 512 or 4096
+    pub fn data_sector_size(&self) -> u32 {
+        if self.0[EXT_CSD_DATA_SECTOR_SIZE_INDEX] == 1 {
+            4096
+        } else {
+            512
+        }
+    }
+
+    pub fn partitioning_supported(&self) -> bool {
+        self.0[EXT_CSD_PARTITIONING_SUPPORT_INDEX].get_bit(0)
+    }
+
+    pub fn partition_setting_completed(&self) -> bool {
+        self.0[EXT_CSD_PARTITION_SETTING_COMPLETED_INDEX].get_bit(0)
+    }
+
+    /// PARTITION_CONFIG, also named BOOT_CONFIG
+    pub fn partition_config(&self) -> u8 {
+        self.byte(ModeIndex::BootConfig)
+    }
+
+    /// Partition accessed, 0: user area, 1/2: boot, 3: RPMB, 4-7: general purpose
+    pub fn partition_access(&self) -> u8 {
+        self.partition_config().get_bits(0..3)
+    }
+
+    /// Partition enabled for boot, 0: none, 1/2: boot, 7: user area
+    pub fn boot_partition_enable(&self) -> u8 {
+        self.partition_config().get_bits(3..6)
+    }
+
+    pub fn boot_ack(&self) -> bool {
+        self.partition_config().get_bit(6)
+    }
+
+    pub fn boot_bus_conditions(&self) -> u8 {
+        self.byte(ModeIndex::BootBusWidth)
+    }
+
+    pub fn boot_write_protect(&self) -> u8 {
+        self.byte(ModeIndex::BootWp)
+    }
+
+    pub fn boot_write_protect_status(&self) -> u8 {
+        self.0[EXT_CSD_BOOT_WP_STATUS_INDEX]
+    }
+
+    /// Size of each boot partition in KBytes
+    pub fn boot_partition_size(&self) -> u32 {
+        self.0[EXT_CSD_BOOT_SIZE_MULT_INDEX] as u32 * 128
+    }
+
+    /// Size of the RPMB partition in KBytes
+    pub fn rpmb_partition_size(&self) -> u32 {
+        self.0[EXT_CSD_RPMB_SIZE_MULT_INDEX] as u32 * 128
+    }
+
+    /// Size of the general purpose partition `n` (0 to 3) in KBytes
+    pub fn gp_partition_size(&self, n: usize) -> u32 {
+        let mult = self.word(EXT_CSD_GP_SIZE_MULT_INDEX + n * 3, 3);
+        mult * self.hc_write_protect_group_size() as u32 * self.hc_erase_group_size() as u32 * 512
+    }
+
+    /// High capacity erase unit size in 512 KBytes units
+    pub fn hc_erase_group_size(&self) -> u8 {
+        self.0[EXT_CSD_HC_ERASE_GRP_SIZE_INDEX]
+    }
+
+    /// High capacity write protect group size in erase units
+    pub fn hc_write_protect_group_size(&self) -> u8 {
+        self.0[EXT_CSD_HC_WP_GRP_SIZE_INDEX]
+    }
+
+    /// Use of the high capacity erase unit size
+    pub fn erase_group_def(&self) -> bool {
+        self.byte(ModeIndex::EraseGroupDef).get_bit(0)
+    }
+
+    /// High capacity erase timeout in milliseconds
+    pub fn erase_timeout(&self) -> u32 {
+        self.0[EXT_CSD_ERASE_TIMEOUT_MULT_INDEX] as u32 * 300
+    }
+
+    /// Trim timeout in milliseconds
+    pub fn trim_timeout(&self) -> u32 {
+        self.0[EXT_CSD_TRIM_MULT_INDEX] as u32 * 300
+    }
+
+    /// Secure erase timeout in milliseconds
+    pub fn secure_erase_timeout(&self) -> u32 {
+        self.erase_timeout() * self.0[EXT_CSD_SEC_ERASE_MULT_INDEX] as u32
+    }
+
+    /// Secure trim timeout in milliseconds
+    pub fn secure_trim_timeout(&self) -> u32 {
+        self.erase_timeout() * self.0[EXT_CSD_SEC_TRIM_MULT_INDEX] as u32
+    }
+
+    /// Secure erase, secure trim, sanitize and discard support
+    pub fn secure_feature_support(&self) -> u8 {
+        self.0[EXT_CSD_SEC_FEATURE_SUPPORT_INDEX]
+    }
+
+    /// Secure erase and secure trim support
+    pub fn secure_purge_supported(&self) -> bool {
+        self.secure_feature_support().get_bit(0)
+    }
+
+    pub fn sanitize_supported(&self) -> bool {
+        self.secure_feature_support().get_bit(6)
+    }
+
+    /// Trim support, GB_CL_EN
+    pub fn trim_supported(&self) -> bool {
+        self.secure_feature_support().get_bit(4)
+    }
+
+    /// Discard is supported from eMMC 4.5
+    pub fn discard_supported(&self) -> bool {
+        self.revision() >= 6
+    }
+
+    /// Reliable write sector count
+    pub fn reliable_write_sector_count(&self) -> u8 {
+        self.0[EXT_CSD_REL_WR_SEC_C_INDEX]
+    }
+
+    /// Volatile cache size in KBytes
+    pub fn cache_size(&self) -> u32 {
+        self.word(EXT_CSD_CACHE_SIZE_INDEX, 4)
+    }
+
+    pub fn cache_enabled(&self) -> bool {
+        self.byte(ModeIndex::CacheCtrl).get_bit(0)
+    }
+
+    pub fn bkops_supported(&self) -> bool {
+        self.0[EXT_CSD_BKOPS_SUPPORT_INDEX].get_bit(0)
+    }
+
+    pub fn bkops_enabled(&self) -> bool {
+        self.0[EXT_CSD_BKOPS_EN_INDEX].get_bit(0)
+    }
+
+    /// Background operations level, 0: not required, 3: critical
+    pub fn bkops_status(&self) -> u8 {
+        self.0[EXT_CSD_BKOPS_STATUS_INDEX].get_bits(0..2)
+    }
+
+    pub fn hpi_supported(&self) -> bool {
+        self.0[EXT_CSD_HPI_FEATURES_INDEX].get_bit(0)
+    }
+
+    /// HPI is implemented with CMD12 instead of CMD13
+    pub fn hpi_uses_cmd12(&self) -> bool {
+        self.0[EXT_CSD_HPI_FEATURES_INDEX].get_bit(1)
+    }
+
+    pub fn hpi_enabled(&self) -> bool {
+        self.0[EXT_CSD_HPI_MGMT_INDEX].get_bit(0)
+    }
+
+    /// Power class selected
+    pub fn power_class(&self) -> u8 {
+        self.byte(ModeIndex::PowerClass)
+    }
+
+    /// Power class for 52MHz at 1.95V, 4 bits bus in bits 0..4, 8 bits bus in bits 4..8
+    pub fn power_class_52_195(&self) -> u8 {
+        self.0[EXT_CSD_PWR_CL_52_195_INDEX]
+    }
+
+    pub fn power_class_26_195(&self) -> u8 {
+        self.0[EXT_CSD_PWR_CL_26_195_INDEX]
+    }
+
+    pub fn power_class_52_360(&self) -> u8 {
+        self.0[EXT_CSD_PWR_CL_52_360_INDEX]
+    }
+
+    pub fn power_class_26_360(&self) -> u8 {
+        self.0[EXT_CSD_PWR_CL_26_360_INDEX]
+    }
+
+    pub fn power_class_200_195(&self) -> u8 {
+        self.0[EXT_CSD_PWR_CL_200_195_INDEX]
+    }
+
+    pub fn power_class_200_360(&self) -> u8 {
+        self.0[EXT_CSD_PWR_CL_200_360_INDEX]
+    }
+
+    pub fn power_class_ddr_52_195(&self) -> u8 {
+        self.0[EXT_CSD_PWR_CL_DDR_52_195_INDEX]
+    }
+
+    pub fn power_class_ddr_52_360(&self) -> u8 {
+        self.0[EXT_CSD_PWR_CL_DDR_52_360_INDEX]
+    }
+
+    pub fn power_class_ddr_200_360(&self) -> u8 {
+        self.0[EXT_CSD_PWR_CL_DDR_200_360_INDEX]
+    }
+
+    /// Pre end of life, 1: normal, 2: warning, 3: urgent
+    pub fn pre_eol_info(&self) -> u8 {
+        self.0[EXT_CSD_PRE_EOL_INFO_INDEX]
+    }
+
+    /// Life time estimation of SLC area in 10% steps, 0x0B when exceeded
+    pub fn life_time_estimation_a(&self) -> u8 {
+        self.0[EXT_CSD_DEVICE_LIFE_TIME_EST_TYP_A_INDEX]
+    }
+
+    /// Life time estimation of MLC area in 10% steps, 0x0B when exceeded
+    pub fn life_time_estimation_b(&self) -> u8 {
+        self.0[EXT_CSD_DEVICE_LIFE_TIME_EST_TYP_B_INDEX]
+    }
+
+    /// Timing interface selected, 0: backward compatible, 1: high speed, 2: HS200, 3: HS400
+    pub fn hs_timing(&self) -> u8 {
+        self.byte(ModeIndex::HsTimingIndex).get_bits(0..4)
+    }
+
+    /// Partition switch timeout in milliseconds
+    pub fn partition_switch_time(&self) -> u32 {
+        self.0[EXT_CSD_PARTITION_SWITCH_TIME_INDEX] as u32 * 10
+    }
+
+    /// Default CMD6 timeout in milliseconds
+    pub fn generic_cmd6_time(&self) -> u32 {
+        self.0[EXT_CSD_GENERIC_CMD6_TIME_INDEX] as u32 * 10
+    }
+
+    /// High priority interrupt timeout in milliseconds
+    pub fn out_of_interrupt_time(&self) -> u32 {
+        self.0[EXT_CSD_OUT_OF_INTERRUPT_TIME_INDEX] as u32 * 10
+    }
+
+    /// Long power off notification timeout in milliseconds
+    pub fn power_off_long_time(&self) -> u32 {
+        self.0[EXT_CSD_POWER_OFF_LONG_TIME_INDEX] as u32 * 10
+    }
+
+    /// Power off notification state, see PowerOffNotification
+    pub fn power_off_notification(&self) -> u8 {
+        self.byte(ModeIndex::PowerOffNotification)
+    }
+
+    /// Power off notification is supported from eMMC 4.5
+    pub fn power_off_notification_supported(&self) -> bool {
+        self.revision() >= 6
+    }
+
+    /// Sleep notification is supported from eMMC 5.0
+    pub fn sleep_notification_supported(&self) -> bool {
+        self.revision() >= 7
+    }
+
+    /// Sleep notification timeout in microseconds
+    pub fn sleep_notification_time(&self) -> u32 {
+        10u32.checked_shl(self.0[EXT_CSD_SLEEP_NOTIFICATION_TIME_INDEX] as u32).unwrap_or(0)
+    }
+
+    /// Sleep/awake timeout in nanoseconds
+    pub fn sleep_awake_timeout(&self) -> u32 {
+        100u32.checked_shl(self.0[EXT_CSD_S_A_TIMEOUT_INDEX] as u32).unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capacity_and_partitions() {
+        let mut ext_csd = ExtCsdRegister::default();
+        // 13.1 GBytes, little endian
+        ext_csd.0[EXT_CSD_SEC_COUNT_INDEX..EXT_CSD_SEC_COUNT_INDEX + 4]
+            .copy_from_slice(&[0x00, 0x00, 0xA4, 0x01]);
+        ext_csd.0[EXT_CSD_BOOT_SIZE_MULT_INDEX] = 0x20;
+        ext_csd.0[EXT_CSD_RPMB_SIZE_MULT_INDEX] = 0x04;
+        ext_csd.0[EXT_CSD_CACHE_SIZE_INDEX..EXT_CSD_CACHE_SIZE_INDEX + 4]
+            .copy_from_slice(&[0x00, 0x10, 0x00, 0x00]);
+        assert_eq!(ext_csd.sector_count(), 0x01A4_0000);
+        assert_eq!(ext_csd.boot_partition_size(), 4096);
+        assert_eq!(ext_csd.rpmb_partition_size(), 512);
+        assert_eq!(ext_csd.cache_size(), 4096);
+    }
+
+    #[test]
+    fn partition_config() {
+        let mut ext_csd = ExtCsdRegister::default();
+        ext_csd.set_byte(ModeIndex::BootConfig, 0x4B);
+        assert_eq!(ext_csd.partition_access(), 3);
+        assert_eq!(ext_csd.boot_partition_enable(), 1);
+        assert!(ext_csd.boot_ack());
+    }
+
+    #[test]
+    fn card_type() {
+        let mut ext_csd = ExtCsdRegister::default();
+        ext_csd.0[EXT_CSD_CARD_TYPE_INDEX] = 0x57;
+        assert!(ext_csd.hs26_supported());
+        assert!(ext_csd.hs52_supported());
+        assert!(ext_csd.ddr52_supported());
+        assert!(ext_csd.hs200_supported());
+        assert!(ext_csd.hs400_supported());
+        ext_csd.0[EXT_CSD_CARD_TYPE_INDEX] = 0x03;
+        assert!(!ext_csd.ddr52_supported());
+        assert!(!ext_csd.hs200_supported());
+        assert!(!ext_csd.hs400_supported());
+    }
+
+    #[test]
+    fn timeouts() {
+        let mut ext_csd = ExtCsdRegister::default();
+        ext_csd.0[EXT_CSD_ERASE_TIMEOUT_MULT_INDEX] = 2;
+        ext_csd.0[EXT_CSD_SEC_ERASE_MULT_INDEX] = 3;
+        ext_csd.0[EXT_CSD_GENERIC_CMD6_TIME_INDEX] = 0x19;
+        ext_csd.0[EXT_CSD_S_A_TIMEOUT_INDEX] = 0x11;
+        assert_eq!(ext_csd.erase_timeout(), 600);
+        assert_eq!(ext_csd.secure_erase_timeout(), 1800);
+        assert_eq!(ext_csd.generic_cmd6_time(), 250);
+        assert_eq!(ext_csd.sleep_awake_timeout(), 13_107_200);
+    }
+
+    #[test]
+    fn features() {
+        let mut ext_csd = ExtCsdRegister::default();
+        ext_csd.0[EXT_CSD_REV_INDEX] = 6;
+        ext_csd.0[EXT_CSD_SEC_FEATURE_SUPPORT_INDEX] = 0x55;
+        ext_csd.set_byte(ModeIndex::CacheCtrl, 1);
+        assert!(ext_csd.power_off_notification_supported());
+        assert!(!ext_csd.sleep_notification_supported());
+        assert!(ext_csd.secure_purge_supported());
+        assert!(ext_csd.sanitize_supported());
+        assert!(ext_csd.cache_enabled());
+        assert!(ext_csd.trim_supported());
+        assert!(ext_csd.discard_supported());
+        ext_csd.0[EXT_CSD_REV_INDEX] = 8;
+        assert!(ext_csd.sleep_notification_supported());
+        ext_csd.0[EXT_CSD_REV_INDEX] = 5;
+        ext_csd.0[EXT_CSD_SEC_FEATURE_SUPPORT_INDEX] = 0x45;
+        assert!(!ext_csd.trim_supported());
+        assert!(!ext_csd.discard_supported());
+    }
+}
@@ -1,4 +1,6 @@
+pub mod cid;
 pub mod csd;
+pub mod ext_csd;
 pub mod ocr;
 pub mod register_address;
 pub mod sd;
@@ -1,3 +1,4 @@
 pub mod card_status;
 pub mod scr;
+pub mod sd_status;
 pub mod switch_status;
@@ -10,16 +10,8 @@ pub struct ScrRegister {
 
 impl From<[u8; 8]> for ScrRegister {
     fn from(val: [u8; 8]) -> Self {
-        ScrRegister {
-            val: (val[0] as u64)
-                + ((val[1] as u64) << 8)
-                + ((val[2] as u64) << 16)
-                + ((val[3] as u64) << 24)
-                + ((val[4] as u64) << 32)
-                + ((val[5] as u64) << 40)
-                + ((val[6] as u64) << 48)
-                + ((val[7] as u64) << 56),
-        }
+        // Sent MSB first
+        ScrRegister { val: u64::from_be_bytes(val) }
     }
 }
 
@@ -100,4 +92,58 @@ impl ScrRegister {
     pub fn sd_command_support(&self) -> u8 {
         self.val.get_bits(32..=33) as u8
     }
+
+    pub fn set_cmd20_supported(&mut self, supported: bool) {
+        self.val.set_bit(32, supported);
+    }
+
+    /// Speed class control command
+    pub fn cmd20_supported(&self) -> bool {
+        self.val.get_bit(32)
+    }
+
+    pub fn set_cmd23_supported(&mut self, supported: bool) {
+        self.val.set_bit(33, supported);
+    }
+
+    /// Set block count command
+    pub fn cmd23_supported(&self) -> bool {
+        self.val.get_bit(33)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_msb_first() {
+        // SD 2.00 card with SD_SPEC3 set, 1 and 4-bit bus
+        let scr = ScrRegister::from([0x02, 0x35, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(scr.val, 0x0235_8000_0000_0000);
+        assert_eq!(scr.structure_version() as u8, ScrRegisterStructureVersion::Version1_0 as u8);
+        assert_eq!(
+            scr.sd_specification_version() as u8,
+            SdPhysicalSpecification::Revision2d00 as u8
+        );
+        assert!(!scr.data_status_after_erase());
+        assert!(scr.spec3());
+    }
+
+    #[test]
+    fn command_support() {
+        // CMD20 and CMD23 supported
+        let scr = ScrRegister::from([0x02, 0x35, 0x80, 0x03, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(scr.sd_command_support(), 3);
+        assert!(scr.cmd20_supported());
+        assert!(scr.cmd23_supported());
+
+        let mut scr = ScrRegister::from([0u8; 8]);
+        scr.set_cmd23_supported(true);
+        assert_eq!(scr.val, 1 << 33);
+        assert!(!scr.cmd20_supported());
+        scr.set_cmd20_supported(true);
+        scr.set_cmd23_supported(false);
+        assert_eq!(scr.val, 1 << 32);
+    }
 }
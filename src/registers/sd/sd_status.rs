@@ -0,0 +1,243 @@
+use crate::command_arguments::mmc::BusWidth;
+use bit_field::BitArray;
+
+// AU size codes in KBytes
+const AU_SIZES: [u32; 16] =
+    [0, 16, 32, 64, 128, 256, 512, 1024, 2048, 4096, 8192, 12288, 16384, 24576, 32768, 65536];
+
+/// SD Status register, 512 bits
+pub struct SdStatusRegister {
+    pub val: [u32; 16],
+}
+
+impl From<[u8; 64]> for SdStatusRegister {
+    fn from(val: [u8; 64]) -> Self {
+        // Sent MSB first
+        let mut v = [0u32; 16];
+        for i in 0..64 {
+            v[(63 - i) / 4] |= (val[i] as u32) << (((63 - i) % 4) * 8);
+        }
+        SdStatusRegister { val: v }
+    }
+}
+
+impl SdStatusRegister {
+    pub fn set_bus_width(&mut self, bus_width: BusWidth) {
+        self.val.set_bits(510..512, if bus_width == BusWidth::_4BIT { 2 } else { 0 });
+    }
+
+    pub fn bus_width(&self) -> BusWidth {
+        if self.val.get_bits(510..512) == 2 {
+            BusWidth::_4BIT
+        } else {
+            BusWidth::_1BIT
+        }
+    }
+
+    pub fn set_secured_mode(&mut self, secured: bool) {
+        self.val.set_bits(509..510, secured as u32);
+    }
+
+    pub fn secured_mode(&self) -> bool {
+        self.val.get_bits(509..510) > 0
+    }
+
+    pub fn set_card_type(&mut self, card_type: u16) {
+        self.val.set_bits(480..496, card_type as u32);
+    }
+
+    /// 0: Regular SD RD/WR card, 1: SD ROM card, 2: OTP card
+    pub fn card_type(&self) -> u16 {
+        self.val.get_bits(480..496) as u16
+    }
+
+    pub fn set_protected_area_size(&mut self, size: u32) {
+        self.val.set_bits(448..480, size);
+    }
+
+    /// Size of protected area in bytes for SDHC and SDXC,
+    /// in units of MULT * BLOCK_LEN for SDSC
+    pub fn protected_area_size(&self) -> u32 {
+        self.val.get_bits(448..480)
+    }
+
+    pub fn set_speed_class(&mut self, class: u8) {
+        let code = match class {
+            2 => 1,
+            4 => 2,
+            6 => 3,
+            10 => 4,
+            _ => 0,
+        };
+        self.val.set_bits(440..448, code);
+    }
+
+    /// Speed class in MB/s, 0 for class 0
+    pub fn speed_class(&self) -> u8 {
+        match self.val.get_bits(440..448) {
+            1 => 2,
+            2 => 4,
+            3 => 6,
+            4 => 10,
+            _ => 0,
+        }
+    }
+
+    pub fn set_performance_move(&mut self, performance: u8) {
+        self.val.set_bits(432..440, performance as u32);
+    }
+
+    /// Performance move in MB/s, 0 if not defined and 0xFF for infinity
+    pub fn performance_move(&self) -> u8 {
+        self.val.get_bits(432..440) as u8
+    }
+
+    pub fn set_au_size(&mut self, code: u8) {
+        self.val.set_bits(428..432, code as u32);
+    }
+
+    pub fn au_size(&self) -> u8 {
+        self.val.get_bits(428..432) as u8
+    }
+
+    /// Allocation unit size in KBytes, None if not defined
+    pub fn au_size_kbytes(&self) -> Option<u32> {
+        Some(AU_SIZES[self.au_size() as usize]).filter(|&size| size > 0)
+    }
+
+    pub fn set_erase_size(&mut self, size: u16) {
+        self.val.set_bits(408..424, size as u32);
+    }
+
+    /// Number of AUs to be erased at a time, 0 if erase timeout is not supported
+    pub fn erase_size(&self) -> u16 {
+        self.val.get_bits(408..424) as u16
+    }
+
+    pub fn set_erase_timeout(&mut self, timeout: u8) {
+        self.val.set_bits(402..408, timeout as u32);
+    }
+
+    /// Timeout in seconds of erasing erase_size AUs
+    pub fn erase_timeout(&self) -> u8 {
+        self.val.get_bits(402..408) as u8
+    }
+
+    pub fn set_erase_offset(&mut self, offset: u8) {
+        self.val.set_bits(400..402, offset as u32);
+    }
+
+    /// Erase timeout offset in seconds
+    pub fn erase_offset(&self) -> u8 {
+        self.val.get_bits(400..402) as u8
+    }
+
+    pub fn set_uhs_speed_grade(&mut self, grade: u8) {
+        self.val.set_bits(396..400, grade as u32);
+    }
+
+    /// UHS speed grade, 0: less than 10MB/s, 1: 10MB/s and above, 3: 30MB/s and above
+    pub fn uhs_speed_grade(&self) -> u8 {
+        self.val.get_bits(396..400) as u8
+    }
+
+    pub fn set_uhs_au_size(&mut self, code: u8) {
+        self.val.set_bits(392..396, code as u32);
+    }
+
+    pub fn uhs_au_size(&self) -> u8 {
+        self.val.get_bits(392..396) as u8
+    }
+
+    /// UHS allocation unit size in KBytes, None if not defined
+    pub fn uhs_au_size_kbytes(&self) -> Option<u32> {
+        Some(AU_SIZES[self.uhs_au_size() as usize]).filter(|&size| size >= 1024)
+    }
+
+    pub fn set_video_speed_class(&mut self, class: u8) {
+        self.val.set_bits(384..392, class as u32);
+    }
+
+    /// Video speed class in MB/s: 0, 6, 10, 30, 60 or 90
+    pub fn video_speed_class(&self) -> u8 {
+        self.val.get_bits(384..392) as u8
+    }
+
+    pub fn set_app_performance_class(&mut self, class: u8) {
+        self.val.set_bits(336..340, class as u32);
+    }
+
+    /// Application performance class, 0: not supported, 1: A1, 2: A2
+    pub fn app_performance_class(&self) -> u8 {
+        self.val.get_bits(336..340) as u8
+    }
+
+    pub fn set_discard_support(&mut self, supported: bool) {
+        self.val.set_bit(313, supported);
+    }
+
+    pub fn discard_support(&self) -> bool {
+        self.val.get_bit(313)
+    }
+
+    pub fn set_fule_support(&mut self, supported: bool) {
+        self.val.set_bit(312, supported);
+    }
+
+    /// Full User area Logical Erase support
+    pub fn fule_support(&self) -> bool {
+        self.val.get_bit(312)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_msb_first() {
+        let mut raw = [0u8; 64];
+        raw[0] = 0x80; // 4-bit bus
+        raw[4..8].copy_from_slice(&[0x00, 0x00, 0x10, 0x00]);
+        raw[8] = 0x04; // class 10
+        raw[9] = 0x05;
+        raw[10] = 0x90; // AU 4 MBytes
+        raw[11..13].copy_from_slice(&[0x00, 0x08]);
+        raw[13] = (10 << 2) | 1;
+        raw[14] = 0x10;
+        raw[15] = 30;
+        raw[21] = 0x02;
+        let status = SdStatusRegister::from(raw);
+        assert!(status.bus_width() == BusWidth::_4BIT);
+        assert!(!status.secured_mode());
+        assert_eq!(status.card_type(), 0);
+        assert_eq!(status.protected_area_size(), 0x1000);
+        assert_eq!(status.speed_class(), 10);
+        assert_eq!(status.performance_move(), 5);
+        assert_eq!(status.au_size(), 9);
+        assert_eq!(status.au_size_kbytes(), Some(4096));
+        assert_eq!(status.erase_size(), 8);
+        assert_eq!(status.erase_timeout(), 10);
+        assert_eq!(status.erase_offset(), 1);
+        assert_eq!(status.uhs_speed_grade(), 1);
+        assert_eq!(status.uhs_au_size(), 0);
+        assert_eq!(status.video_speed_class(), 30);
+        assert_eq!(status.app_performance_class(), 2);
+    }
+
+    #[test]
+    fn setters_round_trip() {
+        let mut status = SdStatusRegister::from([0u8; 64]);
+        status.set_bus_width(BusWidth::_4BIT);
+        status.set_speed_class(6);
+        status.set_erase_timeout(0x3F);
+        status.set_erase_offset(3);
+        assert!(status.bus_width() == BusWidth::_4BIT);
+        assert_eq!(status.speed_class(), 6);
+        assert_eq!(status.erase_timeout(), 0x3F);
+        assert_eq!(status.erase_offset(), 3);
+        assert_eq!(status.val[15], 0x8000_0000);
+        assert_eq!(status.val[13], 0x0300_0000);
+        assert_eq!(status.val[12], 0x00FF_0000);
+    }
+}
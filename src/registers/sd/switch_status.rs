@@ -8,9 +8,10 @@ pub struct SwitchStatusRegister {
 
 impl From<[u8; 64]> for SwitchStatusRegister {
     fn from(val: [u8; 64]) -> Self {
+        // Sent MSB first
         let mut v = [0u16; 32];
         for i in 0..64 {
-            v[i / 2] |= (val[i] as u16) << ((i % 2) * 8);
+            v[(63 - i) / 2] |= (val[i] as u16) << (((63 - i) % 2) * 8);
         }
         SwitchStatusRegister { val: v }
     }
@@ -57,12 +58,31 @@ impl SwitchStatusRegister {
         self.val.get_bits(432..448) as u16
     }
 
+    pub fn set_group2_info_status(&mut self, val: u16) {
+        self.val.set_bits(416..432, val);
+    }
+
+    pub fn group2_info_status(&self) -> u16 {
+        self.val.get_bits(416..432) as u16
+    }
+
+    #[deprecated(note = "sets the function group 2 support, use set_group1_function_support")]
     pub fn set_group1_info_status(&mut self, val: u16) {
         self.val.set_bits(416..432, val);
     }
 
+    #[deprecated(note = "returns the function group 2 support, use group1_function_support")]
     pub fn group1_info_status(&self) -> u16 {
-        self.val.get_bits(416..432) as u16
+        self.val.get_bits(416..432)
+    }
+
+    pub fn set_group1_function_support(&mut self, val: u16) {
+        self.val.set_bits(400..416, val);
+    }
+
+    /// Functions supported in group 1, bit N for function N
+    pub fn group1_function_support(&self) -> u16 {
+        self.val.get_bits(400..416)
     }
 
     pub fn set_group6_rc(&mut self, val: u8) {
@@ -169,3 +189,43 @@ impl SwitchStatusRegister {
         self.val.get_bits(272..288) as u16
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_msb_first() {
+        let mut raw = [0u8; 64];
+        raw[0..2].copy_from_slice(&[0x00, 0x64]); // 100 mA
+        raw[16] = 0x01; // high speed selected in group 1
+        raw[17] = 0x01;
+        raw[28..30].copy_from_slice(&[0x00, 0x02]);
+        let status = SwitchStatusRegister::from(raw);
+        assert_eq!(status.max_current_consumption(), 100);
+        assert_eq!(status.group2_rc(), 0);
+        assert_eq!(status.group1_rc(), 1);
+        assert_eq!(status.structure_version(), 1);
+        assert_eq!(status.group1_busy(), 0x0002);
+    }
+
+    #[test]
+    fn group1_error() {
+        let mut raw = [0u8; 64];
+        raw[16] = 0x0F;
+        let status = SwitchStatusRegister::from(raw);
+        assert_eq!(status.group1_rc() as u16, SD_SW_STATUS_FUN_GRP_RC_ERROR);
+        assert_eq!(status.group2_rc(), 0);
+    }
+
+    #[test]
+    fn function_support() {
+        let mut raw = [0u8; 64];
+        raw[10..12].copy_from_slice(&[0x80, 0x01]);
+        raw[12..14].copy_from_slice(&[0x80, 0x03]); // default and high speed
+        let status = SwitchStatusRegister::from(raw);
+        assert_eq!(status.group2_info_status(), 0x8001);
+        assert_eq!(status.group1_function_support(), 0x8003);
+        assert_eq!(status.group1_info_status(), 0x8001);
+    }
+}
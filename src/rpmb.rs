@@ -0,0 +1,48 @@
+use embedded_error::mci::MciError;
+
+use crate::command_arguments::rpmb::{RPMB_KEY_SIZE, RPMB_NONCE_SIZE};
+
+/// Holder of the RPMB authentication key, the key may never leave a secure element
+pub trait RpmbKey {
+    /// HMAC-SHA256 of the concatenated `frames` with the authentication key
+    fn mac(&mut self, frames: &[&[u8]]) -> Result<[u8; RPMB_KEY_SIZE], MciError>;
+
+    /// A nonce never used before, for read requests
+    fn nonce(&mut self) -> [u8; RPMB_NONCE_SIZE];
+}
+
+/// Software HMAC-SHA256 with the key in memory
+#[cfg(feature = "rpmb-hmac")]
+pub struct SoftwareRpmbKey {
+    key: [u8; RPMB_KEY_SIZE],
+    nonce: u128,
+}
+
+#[cfg(feature = "rpmb-hmac")]
+impl SoftwareRpmbKey {
+    /// Nonces are incremented from `seed`, which shall be random and drawn again on every boot,
+    /// e.g. from a hardware RNG. A seed reused across boots repeats nonces, which allows replaying
+    /// recorded read responses.
+    pub fn new(key: [u8; RPMB_KEY_SIZE], seed: [u8; RPMB_NONCE_SIZE]) -> Self {
+        Self { key, nonce: u128::from_be_bytes(seed) }
+    }
+}
+
+#[cfg(feature = "rpmb-hmac")]
+impl RpmbKey for SoftwareRpmbKey {
+    fn mac(&mut self, frames: &[&[u8]]) -> Result<[u8; RPMB_KEY_SIZE], MciError> {
+        use embedded_error::ImplError;
+        use hmac::{Hmac, Mac};
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(&self.key)
+            .map_err(|_| MciError::Impl(ImplError::InvalidConfiguration))?;
+        for frame in frames.iter() {
+            mac.update(frame);
+        }
+        Ok(mac.finalize().into_bytes().into())
+    }
+
+    fn nonce(&mut self) -> [u8; RPMB_NONCE_SIZE] {
+        self.nonce = self.nonce.wrapping_add(1);
+        self.nonce.to_be_bytes()
+    }
+}
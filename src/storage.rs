@@ -0,0 +1,86 @@
+use embedded_error::mci::MciError;
+use embedded_hal::digital::v2::InputPin;
+use embedded_storage::{ReadStorage, Storage};
+
+use crate::bus::{Adtc, Bus, Read, Write, SD_MMC_BLOCK_SIZE};
+use crate::controller::Controller;
+
+/// Byte addressed storage backed by an initialized card
+/// Unaligned accesses are done with read-modify-write of whole blocks
+pub struct CardStorage<BUS, WP, DETECT> {
+    controller: Controller<BUS, WP, DETECT>,
+    buffer: [u8; SD_MMC_BLOCK_SIZE],
+}
+
+impl<BUS, WP, DETECT> CardStorage<BUS, WP, DETECT> {
+    pub fn new(controller: Controller<BUS, WP, DETECT>) -> Self {
+        Self { controller, buffer: [0u8; SD_MMC_BLOCK_SIZE] }
+    }
+
+    /// Release the controller
+    pub fn free(self) -> Controller<BUS, WP, DETECT> {
+        self.controller
+    }
+}
+
+impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> ReadStorage
+    for CardStorage<BUS, WP, DETECT>
+{
+    type Error = MciError;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), MciError> {
+        let mut position = 0;
+        while position < bytes.len() {
+            let address = offset as usize + position;
+            let block = (address / SD_MMC_BLOCK_SIZE) as u32;
+            let start = address % SD_MMC_BLOCK_SIZE;
+            let remain = bytes.len() - position;
+            if start == 0 && remain >= SD_MMC_BLOCK_SIZE {
+                // Aligned blocks are read in place
+                let length = remain - remain % SD_MMC_BLOCK_SIZE;
+                self.controller.read_blocks(block, &mut bytes[position..position + length])?;
+                position += length;
+            } else {
+                let length = remain.min(SD_MMC_BLOCK_SIZE - start);
+                self.controller.read_blocks(block, &mut self.buffer)?;
+                bytes[position..position + length]
+                    .copy_from_slice(&self.buffer[start..start + length]);
+                position += length;
+            }
+        }
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        // Capacity in KBytes
+        (self.controller.card.capacity as usize).saturating_mul(1024)
+    }
+}
+
+impl<BUS: Adtc + Bus + Read + Write, WP: InputPin, DETECT: InputPin> Storage
+    for CardStorage<BUS, WP, DETECT>
+{
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), MciError> {
+        let mut position = 0;
+        while position < bytes.len() {
+            let address = offset as usize + position;
+            let block = (address / SD_MMC_BLOCK_SIZE) as u32;
+            let start = address % SD_MMC_BLOCK_SIZE;
+            let remain = bytes.len() - position;
+            if start == 0 && remain >= SD_MMC_BLOCK_SIZE {
+                // Aligned blocks are written in place
+                let length = remain - remain % SD_MMC_BLOCK_SIZE;
+                self.controller.write_blocks(block, &bytes[position..position + length])?;
+                position += length;
+            } else {
+                let length = remain.min(SD_MMC_BLOCK_SIZE - start);
+                self.controller.read_blocks(block, &mut self.buffer)?;
+                self.buffer[start..start + length]
+                    .copy_from_slice(&bytes[position..position + length]);
+                self.controller.write_blocks(block, &self.buffer)?;
+                position += length;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,10 +1,12 @@
 pub struct Transaction {
     pub total: u16,
     pub remain: u16,
+    /// Block count set with CMD23, no stop command is needed at the end of the transfer
+    pub predefined: bool,
 }
 
 impl Transaction {
     pub fn new(num_blocks: u16) -> Self {
-        Self { total: num_blocks, remain: num_blocks }
+        Self { total: num_blocks, remain: num_blocks, predefined: false }
     }
 }